@@ -12,18 +12,40 @@ use zk_op::Risc0Operator;
 pub mod mem;
 pub use mem::*;
 
+/// Prints the cycles spent in each region for the host to collect as cycle tracker regions.
+fn report_regions(regions: &[(&str, u64)]) {
+    for (name, cycles) in regions {
+        println!("cycle-tracker-report: {name} {cycles}");
+    }
+}
+
 fn main() {
+    // The regions are measured with `env::cycle_count` and only printed once the program is done,
+    // so the printing is not counted in any region.
+    let start = env::cycle_count();
     let input: GuestInput = env::read();
+    let input_cycles = env::cycle_count() - start;
 
     revm_precompile::zk_op::ZKVM_OPERATOR.get_or_init(|| Box::new(Risc0Operator {}));
     revm_precompile::zk_op::ZKVM_OPERATIONS
         .set(Box::new(vec![ZkOperation::Sha256, ZkOperation::Secp256k1]))
         .expect("Failed to set ZkvmOperations");
 
+    let start = env::cycle_count();
     let header = calculate_block_header(&input);
+    let header_cycles = env::cycle_count() - start;
+
+    let start = env::cycle_count();
     let pi = ProtocolInstance::new(&input, &header, ProofType::Risc0)
         .unwrap()
         .instance_hash();
+    let protocol_instance_cycles = env::cycle_count() - start;
 
     env::commit(&pi);
+
+    report_regions(&[
+        ("input", input_cycles),
+        ("calculate_block_header", header_cycles),
+        ("ProtocolInstance", protocol_instance_cycles),
+    ]);
 }
@@ -26,4 +26,7 @@ fn main() {
     let block_hash = header.hash_slow();
 
     println!("block_hash: {:?}", block_hash);
+
+    // Commit the block hash.
+    env::commit(&block_hash.0);
 }
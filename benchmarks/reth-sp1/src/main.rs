@@ -25,4 +25,7 @@ fn main() {
     let block_hash = header.hash_slow();
 
     println!("block_hash: {:?}", block_hash);
+
+    // Commit the block hash.
+    sp1_zkvm::io::commit(&block_hash.0);
 }
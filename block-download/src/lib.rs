@@ -0,0 +1,226 @@
+//! The retries and the summary shared by the block downloaders.
+
+use std::{fmt::Display, future::Future, time::Duration};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    #[error("{0} blocks failed to download")]
+    Failed(usize),
+}
+
+/// How often and how patiently a failed download is retried.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub retries: u32,
+    /// The delay before the first retry, doubled after every retry.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// The delay before each retry.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> {
+        let backoff = self.backoff;
+        (0..self.retries).map(move |retry| backoff.saturating_mul(2u32.saturating_pow(retry)))
+    }
+
+    /// Runs an operation until it succeeds or the retries run out, returning the last error. Only
+    /// the errors `is_transient` accepts are retried, the others are returned right away.
+    pub async fn run<T, E, F, Fut>(
+        &self,
+        label: &str,
+        is_transient: impl Fn(&E) -> bool,
+        mut operation: F,
+    ) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut delays = self.delays().enumerate();
+        loop {
+            let err = match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if !is_transient(&err) {
+                return Err(err);
+            }
+            let Some((retry, delay)) = delays.next() else {
+                return Err(err);
+            };
+            tracing::warn!(
+                "{} failed, retrying in {:?} ({}/{}): {:#}",
+                label,
+                delay,
+                retry + 1,
+                self.retries,
+                err
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Parts of the messages of RPC and transport errors that may succeed when retried: timeouts,
+/// dropped connections, rate limits and unavailable servers. Compared in lowercase.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "timed out",
+    "timeout",
+    "error sending request",
+    "connection",
+    "broken pipe",
+    "too many requests",
+    "rate limit",
+    "bad gateway",
+    "service unavailable",
+    "temporarily unavailable",
+    "http error 429",
+    "http error 502",
+    "http error 503",
+    "http error 504",
+];
+
+/// Whether a failed download may succeed when retried. The executors of both downloaders turn the
+/// RPC errors into messages, so they are told apart by their message rather than their type.
+pub fn is_transient(err: &impl Display) -> bool {
+    let message = format!("{err:#}").to_lowercase();
+    TRANSIENT_ERRORS
+        .iter()
+        .any(|transient| message.contains(transient))
+}
+
+/// The outcome of downloading a set of blocks.
+#[derive(Default)]
+pub struct DownloadSummary {
+    pub downloaded: usize,
+    /// Blocks that were on disk already.
+    pub skipped: usize,
+    /// Blocks that failed after all retries, with their last error.
+    pub failed: Vec<(u64, String)>,
+}
+
+impl DownloadSummary {
+    /// Logs the summary, failing if any block could not be downloaded.
+    pub fn finish(mut self) -> Result<(), DownloadError> {
+        tracing::info!(
+            "Downloaded {} blocks, skipped {} already downloaded, {} failed",
+            self.downloaded,
+            self.skipped,
+            self.failed.len()
+        );
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+
+        self.failed.sort_by_key(|(block_number, _)| *block_number);
+        for (block_number, err) in &self.failed {
+            tracing::error!("Block {}: {}", block_number, err);
+        }
+        Err(DownloadError::Failed(self.failed.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use tokio::time::Instant;
+
+    use super::*;
+
+    fn policy(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn doubles_the_backoff_after_every_retry() {
+        let delays: Vec<_> = policy(4).delays().map(|delay| delay.as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800]);
+        assert_eq!(policy(0).delays().count(), 0);
+        // The delays saturate rather than overflow.
+        let longest = Duration::from_millis(100) * u32::MAX;
+        assert_eq!(policy(40).delays().last(), Some(longest));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_the_retries_run_out() {
+        let attempts = Cell::new(0);
+        let start = Instant::now();
+        let result: Result<(), String> = policy(3)
+            .run("Block 1", is_transient, || async {
+                attempts.set(attempts.get() + 1);
+                Err(format!("attempt {} timed out", attempts.get()))
+            })
+            .await;
+
+        assert_eq!(result, Err("attempt 4 timed out".to_string()));
+        assert_eq!(attempts.get(), 4);
+        assert_eq!(start.elapsed(), Duration::from_millis(100 + 200 + 400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_retrying_once_the_operation_succeeds() {
+        let attempts = Cell::new(0);
+        let start = Instant::now();
+        let result = policy(3)
+            .run("Block 1", is_transient, || async {
+                attempts.set(attempts.get() + 1);
+                match attempts.get() {
+                    1 | 2 => Err("503 Service Unavailable"),
+                    attempt => Ok(attempt),
+                }
+            })
+            .await;
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(start.elapsed(), Duration::from_millis(100 + 200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_retry_permanent_errors() {
+        let attempts = Cell::new(0);
+        let start = Instant::now();
+        let result: Result<(), _> = policy(3)
+            .run("Block 1", is_transient, || async {
+                attempts.set(attempts.get() + 1);
+                Err("block 7 not found")
+            })
+            .await;
+
+        assert_eq!(result, Err("block 7 not found"));
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn tells_transient_errors_apart() {
+        assert!(is_transient(
+            &"error sending request for url (https://rpc.test/)"
+        ));
+        assert!(is_transient(&"operation timed out"));
+        assert!(is_transient(&"HTTP error 429 with body: Too Many Requests"));
+        assert!(is_transient(&"502 Bad Gateway"));
+        assert!(!is_transient(&"block 7 not found"));
+        assert!(!is_transient(&"state root mismatch"));
+    }
+
+    #[test]
+    fn fails_if_any_block_failed() {
+        let summary = DownloadSummary {
+            downloaded: 2,
+            skipped: 1,
+            failed: Vec::new(),
+        };
+        assert!(summary.finish().is_ok());
+
+        let summary = DownloadSummary {
+            failed: vec![(9, "timeout".to_string()), (3, "not found".to_string())],
+            ..Default::default()
+        };
+        assert!(matches!(summary.finish(), Err(DownloadError::Failed(2))));
+    }
+}
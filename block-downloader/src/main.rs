@@ -1,25 +1,53 @@
-use std::{
-    fs::{self, File},
-    path::PathBuf,
-};
+use std::{fs, num::NonZeroUsize, path::PathBuf, time::Duration};
 
 use alloy_provider::ReqwestProvider;
+use block_download::{is_transient, DownloadSummary, RetryPolicy};
+use block_manifest::{Manifest, ManifestEntry};
 use clap::Parser;
 use eyre::Result;
+use futures::stream::{self, StreamExt};
+use rpc_cassette::Cassette;
 use rsp_client_executor::ChainVariant;
 use rsp_host_executor::HostExecutor;
 use url::Url;
 
+/// The downloader recorded in the manifest of the blocks it writes.
+const DOWNLOADER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
 #[derive(Parser)]
 #[command(about = "Download blocks and save them to disk")]
 struct Args {
-    /// List of block numbers to download
-    #[arg(required = true)]
+    /// List of block numbers to download. Block 0 has no parent to execute it on
+    #[arg(required = true, value_parser = clap::value_parser!(u64).range(1..))]
     block_numbers: Vec<u64>,
 
     /// RPC URL to download blocks from
     #[arg(long, default_value = "http://localhost:8545")]
     rpc_url: Url,
+
+    /// Number of blocks downloaded at the same time
+    #[arg(long, default_value = "4")]
+    concurrency: NonZeroUsize,
+
+    /// Number of times a download that failed on a transient RPC error is retried
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled after every retry
+    #[arg(long, default_value_t = 1000)]
+    backoff_ms: u64,
+
+    /// Download blocks again even if they are already on disk
+    #[arg(long)]
+    force: bool,
+
+    /// Record every RPC request and response to a cassette file
+    #[arg(long, value_name = "CASSETTE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer RPC requests from a recorded cassette file instead of the RPC
+    #[arg(long, value_name = "CASSETTE")]
+    replay: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -40,21 +68,84 @@ async fn main() -> Result<()> {
     let blocks_dir = PathBuf::from("../eval/blocks");
     fs::create_dir_all(&blocks_dir)?;
 
-    let provider = ReqwestProvider::new_http(args.rpc_url);
+    let mut manifest = Manifest::load(&blocks_dir)?.unwrap_or_default();
+
+    // The manifest records the RPC the blocks come from, even when they are replayed.
+    let rpc = args.rpc_url.to_string();
+    let cassette = match (&args.record, &args.replay) {
+        (Some(path), _) => Some(Cassette::record(path)?),
+        (None, Some(path)) => Some(Cassette::replay(path)?),
+        (None, None) => None,
+    };
+    let rpc_url = match &cassette {
+        Some(cassette) => Url::parse(&cassette.serve("ethereum", &rpc).await?)?,
+        None => args.rpc_url,
+    };
+
+    let provider = ReqwestProvider::new_http(rpc_url);
     let executor = HostExecutor::new(provider);
     let chain = ChainVariant::Ethereum;
 
-    for block_number in args.block_numbers {
-        tracing::info!("Downloading block {}", block_number);
-        let client_input = executor.execute(block_number, chain).await?;
+    let mut summary = DownloadSummary::default();
+    let pending: Vec<u64> = args
+        .block_numbers
+        .into_iter()
+        .filter(|block_number| {
+            let downloaded = !args.force && blocks_dir.join(file_name(*block_number)).exists();
+            if downloaded {
+                tracing::info!("Skipping block {}, already downloaded", block_number);
+                summary.skipped += 1;
+            }
+            !downloaded
+        })
+        .collect();
+
+    let retry_policy = RetryPolicy {
+        retries: args.retries,
+        backoff: Duration::from_millis(args.backoff_ms),
+    };
+    let executor = &executor;
+    let mut downloads = stream::iter(pending)
+        .map(|block_number| async move {
+            let label = format!("Block {}", block_number);
+            let client_input = retry_policy
+                .run(&label, is_transient, || {
+                    tracing::info!("Downloading block {}", block_number);
+                    executor.execute(block_number, chain)
+                })
+                .await;
+            (block_number, client_input)
+        })
+        .buffer_unordered(args.concurrency.get());
+
+    while let Some((block_number, client_input)) = downloads.next().await {
+        let client_input = match client_input {
+            Ok(client_input) => client_input,
+            Err(err) => {
+                tracing::error!("Failed to download block {}: {:#}", block_number, err);
+                summary.failed.push((block_number, format!("{:#}", err)));
+                continue;
+            }
+        };
 
-        let file_path = blocks_dir.join(format!("{}.bin", block_number));
-        let mut file = File::create(file_path)?;
+        let file_name = file_name(block_number);
+        let bytes = bincode::serialize(&client_input)?;
+        fs::write(blocks_dir.join(&file_name), &bytes)?;
 
-        bincode::serialize_into(&mut file, &client_input)?;
+        let entry = ManifestEntry::new(block_number, "ethereum", None, DOWNLOADER, &rpc, &bytes);
+        manifest.blocks.insert(file_name, entry);
+        manifest.save(&blocks_dir)?;
 
         tracing::info!("Successfully saved block {}", block_number);
+        summary.downloaded += 1;
     }
 
-    Ok(())
+    if let Some(cassette) = &cassette {
+        cassette.save()?;
+    }
+    Ok(summary.finish()?)
+}
+
+fn file_name(block_number: u64) -> String {
+    format!("{}.bin", block_number)
 }
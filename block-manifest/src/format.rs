@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+/// The format a Taiko `GuestInput` is stored in, told apart by the extension of the block file.
+///
+/// The downloaders only write JSON, which is decoded by field name and so survives differences
+/// between the raiko revisions of the downloader and `eval`. The binary formats are positional and
+/// are written by `eval convert`, with the `GuestInput` of the raiko revision that reads them.
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// Pretty JSON, as written by `raiko-block-downloader`.
+    Json,
+    /// Bincode, which is faster to decode and smaller than JSON.
+    Bincode,
+    /// Zstandard-compressed bincode.
+    Zstd,
+}
+
+impl BlockFormat {
+    /// The formats in the order they are looked up, fastest to decode first.
+    pub const ALL: [BlockFormat; 3] = [BlockFormat::Bincode, BlockFormat::Zstd, BlockFormat::Json];
+
+    /// The extensions of the formats, in the order of [BlockFormat::ALL].
+    pub const EXTENSIONS: &'static [&'static str] = &["bincode", "bincode.zst", "json"];
+
+    /// The extension of the block files. Bincode blocks use `.bincode` rather than `.bin`, which
+    /// is taken by the `ClientExecutorInput` blocks of `reth`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BlockFormat::Json => "json",
+            BlockFormat::Bincode => "bincode",
+            BlockFormat::Zstd => "bincode.zst",
+        }
+    }
+
+    /// The format of a block file, from its extension.
+    pub fn from_path(path: &Path) -> Option<BlockFormat> {
+        let file_name = path.file_name()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| file_name.ends_with(&format!(".{}", format.extension())))
+    }
+
+    /// The file name of a block in this format.
+    pub fn file_name(&self, block_name: &str) -> String {
+        format!("{block_name}.{}", self.extension())
+    }
+
+    /// The path of a block in this format.
+    pub fn path(&self, blocks_dir: &Path, block_name: &str) -> PathBuf {
+        blocks_dir.join(self.file_name(block_name))
+    }
+}
+
+/// Finds the file of a block in a directory, in the first format of [BlockFormat::ALL] it is
+/// stored in.
+pub fn find_block(blocks_dir: &Path, block_name: &str) -> Option<(PathBuf, BlockFormat)> {
+    BlockFormat::ALL
+        .into_iter()
+        .map(|format| (format.path(blocks_dir, block_name), format))
+        .find(|(path, _)| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn detects_formats_from_extensions() {
+        let format = |path: &str| BlockFormat::from_path(Path::new(path));
+        assert_eq!(format("blocks/7.json"), Some(BlockFormat::Json));
+        assert_eq!(format("blocks/7.bincode"), Some(BlockFormat::Bincode));
+        assert_eq!(format("blocks/7.bincode.zst"), Some(BlockFormat::Zstd));
+        assert_eq!(format("blocks/7.bin"), None);
+        assert_eq!(format("blocks"), None);
+    }
+
+    #[test]
+    fn finds_the_fastest_format_to_decode() {
+        let dir = tempfile::tempdir().unwrap();
+        for format in [BlockFormat::Json, BlockFormat::Zstd] {
+            fs::write(format.path(dir.path(), "7"), b"block").unwrap();
+        }
+
+        let (path, format) = find_block(dir.path(), "7").unwrap();
+        assert_eq!(format, BlockFormat::Zstd);
+        assert_eq!(path, dir.path().join("7.bincode.zst"));
+        assert!(find_block(dir.path(), "8").is_none());
+    }
+}
@@ -0,0 +1,120 @@
+#!/bin/bash
+# Builds the guest of a program for a prover. Sourced by eval.sh and run once per guest by
+# `eval sweep`.
+set -e
+
+PROGRAM=$1;
+PROVER=$2;
+
+# Function to check rust version and determine correct parameter name
+check_rust_version() {
+    local toolchain=$PROGRAM
+    local version_output
+
+    if [ -z "$toolchain" ]; then
+        version_output=$(rustc --version)
+    else
+        version_output=$(rustc +$toolchain --version)
+    fi
+
+    # Extract version number
+    local version=$(echo "$version_output" | sed -E 's/rustc ([0-9]+\.[0-9]+\.[0-9]+).*/\1/')
+    local major=$(echo "$version" | cut -d. -f1)
+    local minor=$(echo "$version" | cut -d. -f2)
+
+    # Compare version with 1.81
+    if [ "$major" -gt 1 ] || ([ "$major" -eq 1 ] && [ "$minor" -gt 81 ]); then
+        echo "lower-atomic"  # New parameter name for Rust >= 1.81
+    else
+        echo "loweratomic"   # Old parameter name for Rust < 1.81
+    fi
+}
+
+echo "Building program"
+
+if [ "$PROVER" == "native" ]; then
+    # Native execution links the guest logic into the host, there is no guest to build.
+    echo "Skipping guest build for native execution"
+elif [ "$PROGRAM" == "raiko" ]; then
+    echo "Building Raiko for prover $PROVER"
+
+    # Values from Raiko build script
+    TOOLCHAIN_RISC0=nightly-2024-09-05
+    TOOLCHAIN_SP1=nightly-2024-09-05
+
+    # Run a builder inherited from Raiko itself
+    if [ "$PROVER" == "sp1" ]; then
+        GUEST_TOOLCHAIN=$TOOLCHAIN_SP1
+        RUSTUP_TOOLCHAIN=$TOOLCHAIN_SP1 \
+            cargo run --bin raiko-sp1-builder
+    elif [ "$PROVER" == "risc0" ]; then
+        GUEST_TOOLCHAIN=$TOOLCHAIN_RISC0
+        RUSTUP_TOOLCHAIN=$TOOLCHAIN_RISC0 \
+            cargo run --bin raiko-risc0-builder
+    else
+        echo "Prover $PROVER is not supported for Raiko benchmark!"
+        exit
+    fi
+else
+    # Get program directory name as $PROGRAM and append "-$PROVER" to it if $PROGRAM is "tendermint"
+    # or "reth"
+    if [ "$PROGRAM" = "tendermint" ] || [ "$PROGRAM" = "reth" ]; then
+        program_directory="${1}-$PROVER"
+    else
+        program_directory="$PROGRAM"
+    fi
+
+    # cd to program directory computed above
+    cd "benchmarks/$program_directory"
+
+    # If the prover is risc0, then build the program.
+    if [ "$PROVER" == "risc0" ]; then
+        echo "Building Risc0"
+        GUEST_TOOLCHAIN=risc0
+        # Use the risc0 toolchain.
+        ATOMIC_PARAM=$(check_rust_version "risc0")
+        CC=gcc CC_riscv32im_risc0_zkvm_elf=~/.risc0/cpp/bin/riscv32-unknown-elf-gcc\
+          RUSTFLAGS="-C passes=$ATOMIC_PARAM -C link-arg=-Ttext=0x00200800 -C panic=abort"\
+          RISC0_FEATURE_bigint2=1\
+          RUSTUP_TOOLCHAIN=risc0 \
+          CARGO_BUILD_TARGET=riscv32im-risc0-zkvm-elf \
+          cargo build --release --ignore-rust-version --features $PROVER
+    fi
+
+    # If the prover is sp1, then build the program.
+    if [ "$PROVER" == "sp1" ]; then
+        # The reason we don't just use `cargo prove build` from the SP1 CLI is we need to pass a --features ...
+        # flag to select between sp1 and risc0.
+        ATOMIC_PARAM=$(check_rust_version "succinct")
+        GUEST_TOOLCHAIN=succinct
+        RUSTFLAGS="-C passes=$ATOMIC_PARAM -C link-arg=-Ttext=0x00200800 -C panic=abort" \
+            RUSTUP_TOOLCHAIN=succinct \
+            CARGO_BUILD_TARGET=riscv32im-succinct-zkvm-elf \
+            cargo build --release --ignore-rust-version --features $PROVER
+    fi
+
+    if [ "$PROVER" == "lita" ]; then
+      echo "Building Lita"
+      # Use the lita toolchain.
+      CC_valida_unknown_baremetal_gnu="/valida-toolchain/bin/clang" \
+        CFLAGS_valida_unknown_baremetal_gnu="--sysroot=/valida-toolchain -isystem /valida-toolchain/include" \
+        RUSTUP_TOOLCHAIN=valida \
+        CARGO_BUILD_TARGET=valida-unknown-baremetal-gnu \
+        cargo build --release --ignore-rust-version --features $PROVER
+    fi
+
+    if [ "$PROVER" == "nexus" ]; then
+      echo "Building Nexus"
+      # Hardcode the memlimit to 8 MB
+      GUEST_TOOLCHAIN=1.77.0
+      RUSTFLAGS="-C link-arg=--defsym=MEMORY_LIMIT=0x80000 -C link-arg=-T../../nova.x" \
+        CARGO_BUILD_TARGET=riscv32i-unknown-none-elf \
+        RUSTUP_TOOLCHAIN=1.77.0 \
+        cargo build --release --ignore-rust-version --features $PROVER
+    fi
+
+    cd ../../
+fi
+
+# Report the toolchain to `eval sweep`, which records it in the metadata of the runs.
+echo "GUEST_TOOLCHAIN=$GUEST_TOOLCHAIN"
@@ -1,5 +1,15 @@
+use std::{env, fs};
+
 use vergen_git2::{BuildBuilder, Emitter, Git2Builder};
 
+/// The crates whose resolved versions are recorded in the run metadata.
+const VERSIONED_CRATES: &[(&str, &str)] = &[
+    ("sp1-sdk", "EVAL_SP1_SDK_VERSION"),
+    ("risc0-zkvm", "EVAL_RISC0_ZKVM_VERSION"),
+    ("jolt-sdk", "EVAL_JOLT_SDK_VERSION"),
+    ("nexus-sdk", "EVAL_NEXUS_SDK_VERSION"),
+];
+
 fn main() {
     let mut config = Emitter::default();
 
@@ -15,4 +25,43 @@ fn main() {
     config.add_instructions(&git_config).unwrap();
 
     config.emit().unwrap();
+
+    // Record the flags the harness is built with, they are separated by 0x1f.
+    let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    println!(
+        "cargo:rustc-env=EVAL_RUSTFLAGS={}",
+        rustflags.replace('\x1f', " ")
+    );
+
+    // Record the resolved versions of the zkVM crates.
+    println!("cargo:rerun-if-changed=../Cargo.lock");
+    let lock = fs::read_to_string("../Cargo.lock").unwrap_or_default();
+    for (name, var) in VERSIONED_CRATES {
+        if let Some(version) = locked_version(&lock, name) {
+            println!("cargo:rustc-env={}={}", var, version);
+        }
+    }
+}
+
+/// Finds the version of a package in `Cargo.lock`, along with the commit for git dependencies.
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let package = lock.split("[[package]]").find(|package| {
+        package
+            .lines()
+            .any(|line| line == format!("name = \"{name}\""))
+    })?;
+    let field = |key: &str| {
+        package.lines().find_map(|line| {
+            line.strip_prefix(key)?
+                .strip_prefix(" = \"")?
+                .strip_suffix('"')
+                .map(str::to_string)
+        })
+    };
+
+    let version = field("version")?;
+    match field("source").and_then(|source| Some(source.split_once('#')?.1.to_string())) {
+        Some(commit) => Some(format!("{version} ({commit})")),
+        None => Some(version),
+    }
 }
@@ -0,0 +1,337 @@
+use std::ops::RangeInclusive;
+
+use serde::Serialize;
+
+use crate::{error::EvalError, stats::Statistic, utils::list_blocks, EvalArgs, PerformanceReport};
+
+/// The inputs of an evaluation: each block of a batch, or the single input of the arguments.
+pub fn inputs(args: &EvalArgs) -> Result<Vec<EvalArgs>, EvalError> {
+    if !args.is_batch() {
+        return Ok(vec![args.clone()]);
+    }
+
+    let Some((blocks_dir, extensions)) = args.program.blocks_dir(args)? else {
+        return Err(EvalError::MissingInput(format!(
+            "the {} program does not take blocks",
+            args.program
+        )));
+    };
+    let inputs: Vec<_> = list_blocks(&blocks_dir, extensions)?
+        .into_iter()
+        .filter(|block| match &args.block_range {
+            Some(range) => block.parse().is_ok_and(|number| range.contains(&number)),
+            None => true,
+        })
+        .map(|block| EvalArgs {
+            block_name: Some(block),
+            ..args.clone()
+        })
+        .collect();
+    if inputs.is_empty() {
+        return Err(EvalError::MissingInput(format!(
+            "no blocks to evaluate in {}",
+            blocks_dir.display()
+        )));
+    }
+
+    tracing::info!(
+        "Evaluating {} blocks from {}",
+        inputs.len(),
+        blocks_dir.display()
+    );
+    Ok(inputs)
+}
+
+/// Parses an inclusive range of block numbers written as `<first>..<last>`. Used as the clap value
+/// parser for `--block-range`.
+pub fn parse_block_range(range: &str) -> Result<RangeInclusive<u64>, String> {
+    let (first, last) = range
+        .split_once("..")
+        .ok_or_else(|| format!("invalid block range '{range}', expected <first>..<last>"))?;
+    let parse = |number: &str| {
+        number
+            .parse::<u64>()
+            .map_err(|err| format!("invalid block number '{number}': {err}"))
+    };
+    Ok(parse(first)?..=parse(last)?)
+}
+
+/// The totals of a batch of blocks evaluated with one setup, and the statistics of the cycles per
+/// gas of its blocks.
+#[derive(Debug, Serialize)]
+pub struct BatchSummaryReport {
+    /// The program that is being evaluated.
+    pub program: String,
+    /// The prover that is being evaluated.
+    pub prover: String,
+    /// The shard size that is being evaluated.
+    pub shard_size: u64,
+    /// The number of blocks that were evaluated.
+    pub blocks: usize,
+    /// The number of blocks that failed, which are left out of the totals.
+    pub failed_blocks: usize,
+    /// The total number of cycles, empty if it is unknown for some blocks.
+    pub cycles: Option<u64>,
+    /// The total gas used, empty if it is unknown for some blocks.
+    pub gas_used: Option<u64>,
+    /// The total number of transactions, empty if it is unknown for some blocks.
+    pub transactions: Option<usize>,
+    /// The total duration of the execution in seconds.
+    pub execution_duration: f64,
+    /// The total duration of the prover in seconds, empty if nothing was proven.
+    pub prove_duration: Option<f64>,
+    /// The total cycles per unit of gas.
+    pub cycles_per_gas: Option<f64>,
+    /// The total proving duration in seconds per million gas.
+    pub prove_seconds_per_mgas: Option<f64>,
+    /// The mean of the cycles per gas of the blocks.
+    pub cycles_per_gas_mean: Option<f64>,
+    /// The median of the cycles per gas of the blocks.
+    pub cycles_per_gas_median: Option<f64>,
+    /// The standard deviation of the cycles per gas of the blocks.
+    pub cycles_per_gas_stddev: Option<f64>,
+    /// The minimum of the cycles per gas of the blocks.
+    pub cycles_per_gas_min: Option<f64>,
+    /// The maximum of the cycles per gas of the blocks.
+    pub cycles_per_gas_max: Option<f64>,
+}
+
+/// Collects the reports of the blocks of a batch as they are evaluated.
+#[derive(Default)]
+pub struct BatchSummary {
+    blocks: Vec<PerformanceReport>,
+    failed_blocks: usize,
+}
+
+impl BatchSummary {
+    /// Adds the reports of a block, summarised by their mean when there are several trials.
+    pub fn add(&mut self, reports: &[PerformanceReport]) {
+        let report = reports
+            .iter()
+            .find(|report| matches!(report.statistic, None | Some(Statistic::Mean)));
+        if let Some(report) = report {
+            self.blocks.push(report.clone());
+        }
+    }
+
+    pub fn add_failure(&mut self) {
+        self.failed_blocks += 1;
+    }
+
+    pub fn failed_blocks(&self) -> usize {
+        self.failed_blocks
+    }
+
+    pub fn report(&self, args: &EvalArgs) -> BatchSummaryReport {
+        let cycles = self
+            .blocks
+            .iter()
+            .map(|block| block.cycles)
+            .sum::<Option<u64>>()
+            .filter(|_| !self.blocks.is_empty());
+        let gas_used = self
+            .blocks
+            .iter()
+            .map(|block| block.gas_used)
+            .sum::<Option<u64>>()
+            .filter(|_| !self.blocks.is_empty());
+        let transactions = self
+            .blocks
+            .iter()
+            .map(|block| block.transactions)
+            .sum::<Option<usize>>()
+            .filter(|_| !self.blocks.is_empty());
+        let prove_durations: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| block.prove_duration)
+            .collect();
+        let prove_duration = (!prove_durations.is_empty()).then(|| prove_durations.iter().sum());
+        let mgas = gas_used
+            .filter(|gas_used| *gas_used > 0)
+            .map(|gas_used| gas_used as f64 / 1_000_000.0);
+
+        let cycles_per_gas: Vec<f64> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match (block.cycles, block.gas_used) {
+                (Some(cycles), Some(gas_used)) if gas_used > 0 => {
+                    Some(cycles as f64 / gas_used as f64)
+                }
+                _ => None,
+            })
+            .collect();
+        let statistic = |statistic: Statistic| {
+            (!cycles_per_gas.is_empty()).then(|| statistic.compute(&cycles_per_gas))
+        };
+
+        BatchSummaryReport {
+            program: args.program.label(args),
+            prover: args.prover.to_string(),
+            shard_size: args.shard_size,
+            blocks: self.blocks.len(),
+            failed_blocks: self.failed_blocks,
+            cycles,
+            gas_used,
+            transactions,
+            // Summing no durations gives -0.0.
+            execution_duration: self
+                .blocks
+                .iter()
+                .fold(0.0, |total, block| total + block.execution_duration),
+            prove_duration,
+            cycles_per_gas: cycles
+                .zip(gas_used.filter(|gas_used| *gas_used > 0))
+                .map(|(cycles, gas_used)| cycles as f64 / gas_used as f64),
+            prove_seconds_per_mgas: prove_duration
+                .zip(mgas)
+                .map(|(prove_duration, mgas)| prove_duration / mgas),
+            cycles_per_gas_mean: statistic(Statistic::Mean),
+            cycles_per_gas_median: statistic(Statistic::Median),
+            cycles_per_gas_stddev: statistic(Statistic::Stddev),
+            cycles_per_gas_min: statistic(Statistic::Min),
+            cycles_per_gas_max: statistic(Statistic::Max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestArgs {
+        #[command(flatten)]
+        eval: EvalArgs,
+    }
+
+    fn args() -> EvalArgs {
+        TestArgs::parse_from([
+            "eval",
+            "--program",
+            "reth",
+            "--prover",
+            "sp1",
+            "--shard-size",
+            "21",
+            "--filename",
+            "test",
+        ])
+        .eval
+    }
+
+    fn block(cycles: u64, gas_used: Option<u64>, prove_duration: Option<f64>) -> PerformanceReport {
+        PerformanceReport {
+            cycles: Some(cycles),
+            gas_used,
+            transactions: gas_used.map(|_| 2),
+            execution_duration: 1.5,
+            prove_duration,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("the value is reported");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn parses_block_ranges() {
+        assert_eq!(parse_block_range("10..20"), Ok(10..=20));
+        assert_eq!(parse_block_range("7..7"), Ok(7..=7));
+        assert!(parse_block_range("10").is_err());
+        assert!(parse_block_range("10..").is_err());
+        assert!(parse_block_range("a..20").is_err());
+        assert!(parse_block_range("-1..20").is_err());
+    }
+
+    #[test]
+    fn sums_the_blocks() {
+        let mut summary = BatchSummary::default();
+        summary.add(&[block(3_000, Some(1_000), Some(2.0))]);
+        summary.add(&[block(1_000, Some(1_000), Some(4.0))]);
+        summary.add_failure();
+
+        let report = summary.report(&args());
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.failed_blocks, 1);
+        assert_eq!(report.cycles, Some(4_000));
+        assert_eq!(report.gas_used, Some(2_000));
+        assert_eq!(report.transactions, Some(4));
+        assert_close(Some(report.execution_duration), 3.0);
+        assert_close(report.prove_duration, 6.0);
+        assert_close(report.cycles_per_gas, 2.0);
+        assert_close(report.prove_seconds_per_mgas, 3_000.0);
+        assert_close(report.cycles_per_gas_mean, 2.0);
+        assert_close(report.cycles_per_gas_median, 2.0);
+        assert_close(report.cycles_per_gas_stddev, 2.0f64.sqrt());
+        assert_close(report.cycles_per_gas_min, 1.0);
+        assert_close(report.cycles_per_gas_max, 3.0);
+    }
+
+    #[test]
+    fn leaves_unknown_totals_empty() {
+        let mut summary = BatchSummary::default();
+        summary.add(&[block(3_000, Some(1_000), None)]);
+        summary.add(&[block(1_000, None, None)]);
+
+        let report = summary.report(&args());
+        assert_eq!(report.gas_used, None);
+        assert_eq!(report.transactions, None);
+        assert_eq!(report.prove_duration, None);
+        assert_eq!(report.cycles_per_gas, None);
+        // The statistics only cover the blocks with a known gas.
+        assert_close(report.cycles_per_gas_mean, 3.0);
+    }
+
+    #[test]
+    fn leaves_the_cycles_of_zkvms_without_cycles_empty() {
+        let mut summary = BatchSummary::default();
+        summary.add(&[PerformanceReport {
+            cycles: None,
+            ..block(0, Some(1_000), Some(2.0))
+        }]);
+
+        let report = summary.report(&args());
+        assert_eq!(report.cycles, None);
+        assert_eq!(report.cycles_per_gas, None);
+        assert_eq!(report.cycles_per_gas_mean, None);
+        assert_close(report.prove_seconds_per_mgas, 2_000.0);
+    }
+
+    #[test]
+    fn summarises_trials_by_their_mean() {
+        let mut summary = BatchSummary::default();
+        let trial = |statistic, cycles| PerformanceReport {
+            statistic: Some(statistic),
+            ..block(cycles, Some(1_000), None)
+        };
+        summary.add(&[
+            trial(Statistic::Mean, 2_000),
+            trial(Statistic::Median, 1_000),
+            trial(Statistic::Max, 3_000),
+        ]);
+
+        assert_eq!(summary.report(&args()).cycles, Some(2_000));
+    }
+
+    #[test]
+    fn reports_an_empty_batch() {
+        let mut summary = BatchSummary::default();
+        summary.add_failure();
+
+        let report = summary.report(&args());
+        assert_eq!(report.blocks, 0);
+        assert_eq!(report.failed_blocks, 1);
+        assert_eq!(report.cycles, None);
+        assert_eq!(report.gas_used, None);
+        assert_eq!(report.execution_duration.to_string(), "0");
+        assert_eq!(report.cycles_per_gas_mean, None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{fs, path::Path};
+
+use block_manifest::{sha256, BlockFormat, Manifest, ManifestEntry};
+use eyre::{bail, Result, WrapErr};
+use raiko_lib::input::GuestInput;
+
+use crate::utils::list_blocks;
+
+/// Decodes a Taiko `GuestInput` stored in the given format.
+pub fn decode(format: BlockFormat, bytes: &[u8]) -> Result<GuestInput> {
+    let input = match format {
+        BlockFormat::Json => serde_json::from_slice(bytes)?,
+        BlockFormat::Bincode => bincode::deserialize(bytes)?,
+        BlockFormat::Zstd => bincode::deserialize(&zstd::decode_all(bytes)?)?,
+    };
+    Ok(input)
+}
+
+/// Encodes a Taiko `GuestInput` in the given format.
+pub fn encode(format: BlockFormat, input: &GuestInput) -> Result<Vec<u8>> {
+    let bytes = match format {
+        BlockFormat::Json => serde_json::to_vec_pretty(input)?,
+        BlockFormat::Bincode => bincode::serialize(input)?,
+        BlockFormat::Zstd => zstd::encode_all(
+            &bincode::serialize(input)?[..],
+            zstd::DEFAULT_COMPRESSION_LEVEL,
+        )?,
+    };
+    Ok(bytes)
+}
+
+/// Rewrites the blocks of a directory in the given format, optionally removing the files they
+/// were converted from. Blocks already stored in the format are left as they are.
+///
+/// The manifest of the directory, if any, lists the converted files with the provenance of the
+/// files they were converted from.
+pub fn convert(blocks_dir: &Path, to: BlockFormat, remove_source: bool) -> Result<()> {
+    let blocks = list_blocks(blocks_dir, BlockFormat::EXTENSIONS)?;
+    if blocks.is_empty() {
+        bail!("No blocks to convert in {}", blocks_dir.display());
+    }
+    let mut manifest = Manifest::load(blocks_dir)?;
+
+    for block_name in &blocks {
+        let target = to.path(blocks_dir, block_name);
+        let sources: Vec<_> = BlockFormat::ALL
+            .into_iter()
+            .filter(|format| *format != to)
+            .map(|format| (format.path(blocks_dir, block_name), format))
+            .filter(|(path, _)| path.exists())
+            .collect();
+
+        if !target.exists() {
+            let (source, format) = &sources[0];
+            let bytes = fs::read(source)
+                .wrap_err_with(|| format!("Failed to read {}", source.display()))?;
+            // A corrupted block must not be listed with a fresh hash.
+            block_manifest::verify(source, &bytes)?;
+            let input = decode(*format, &bytes)
+                .wrap_err_with(|| format!("Failed to decode {}", source.display()))?;
+            let converted = encode(to, &input)?;
+            fs::write(&target, &converted)
+                .wrap_err_with(|| format!("Failed to write {}", target.display()))?;
+            if let Some(manifest) = &mut manifest {
+                if let Some(entry) = manifest.blocks.get(&file_name(source)).cloned() {
+                    let entry = ManifestEntry {
+                        sha256: sha256(&converted),
+                        ..entry
+                    };
+                    manifest.blocks.insert(file_name(&target), entry);
+                }
+            }
+            println!(
+                "Converted {} to {} ({} -> {} bytes)",
+                source.display(),
+                target.display(),
+                bytes.len(),
+                converted.len()
+            );
+        }
+
+        if remove_source {
+            for (source, _) in &sources {
+                fs::remove_file(source)
+                    .wrap_err_with(|| format!("Failed to remove {}", source.display()))?;
+                if let Some(manifest) = &mut manifest {
+                    manifest.blocks.remove(&file_name(source));
+                }
+            }
+        }
+
+        // Saved after every block, so the manifest matches the files if a later block fails.
+        if let Some(manifest) = &manifest {
+            manifest.save(blocks_dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest_input() -> GuestInput {
+        let mut input = GuestInput::default();
+        input.chain_spec.name = "taiko_test".to_string();
+        input.block.header.number = 7;
+        input
+    }
+
+    fn assert_decodes_to_guest_input(format: BlockFormat, bytes: &[u8]) {
+        let input = decode(format, bytes).unwrap();
+        assert_eq!(input.chain_spec.name, "taiko_test");
+        assert_eq!(input.block.header.number, 7);
+    }
+
+    /// Writes a manifest listing the JSON file of the block with the given hash.
+    fn list_json_block(blocks_dir: &Path, sha256: String) {
+        let entry = ManifestEntry {
+            block_number: 7,
+            network: "taiko_test".to_string(),
+            l1_network: None,
+            downloader: "raiko-block-downloader 0.1.0".to_string(),
+            rpc: "https://rpc.test".to_string(),
+            sha256,
+        };
+        let mut manifest = Manifest::default();
+        manifest.blocks.insert("7.json".to_string(), entry);
+        manifest.save(blocks_dir).unwrap();
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        for format in BlockFormat::ALL {
+            let bytes = encode(format, &guest_input()).unwrap();
+            assert_decodes_to_guest_input(format, &bytes);
+        }
+    }
+
+    #[test]
+    fn converts_blocks_and_their_manifest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = encode(BlockFormat::Json, &guest_input()).unwrap();
+        fs::write(dir.path().join("7.json"), &json).unwrap();
+        list_json_block(dir.path(), sha256(&json));
+
+        convert(dir.path(), BlockFormat::Zstd, true).unwrap();
+
+        assert!(!dir.path().join("7.json").exists());
+        let target = dir.path().join("7.bincode.zst");
+        let converted = fs::read(&target).unwrap();
+        assert_decodes_to_guest_input(BlockFormat::Zstd, &converted);
+
+        let manifest = Manifest::load(dir.path()).unwrap().unwrap();
+        let names: Vec<_> = manifest.blocks.keys().collect();
+        assert_eq!(names, ["7.bincode.zst"]);
+        assert_eq!(manifest.blocks["7.bincode.zst"].block_number, 7);
+        assert!(block_manifest::verify(&target, &converted).is_ok());
+    }
+
+    #[test]
+    fn refuses_to_convert_a_corrupted_block() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("7.json"),
+            encode(BlockFormat::Json, &guest_input()).unwrap(),
+        )
+        .unwrap();
+        list_json_block(dir.path(), sha256(b"another block"));
+
+        assert!(convert(dir.path(), BlockFormat::Bincode, false).is_err());
+        assert!(!dir.path().join("7.bincode").exists());
+    }
+}
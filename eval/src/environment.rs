@@ -0,0 +1,89 @@
+use std::{env, fs, thread};
+
+use serde::Serialize;
+
+/// The machine, toolchain and build a run was made with, so that results from different machines
+/// can be compared.
+///
+/// The crate versions and `RUSTFLAGS` are captured by the build script, the rest when the run ends.
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct Environment {
+    /// The commit the harness was built from.
+    pub git_sha: &'static str,
+    /// When the harness was built.
+    pub build_timestamp: &'static str,
+    /// The CPU model, from `/proc/cpuinfo`.
+    pub cpu_model: Option<String>,
+    /// The number of CPU cores available to the process.
+    pub cpu_cores: Option<usize>,
+    /// The total memory of the machine in bytes, from `/proc/meminfo`.
+    pub total_memory: Option<u64>,
+    /// The memory available when the run ended in bytes, from `/proc/meminfo`.
+    pub available_memory: Option<u64>,
+    /// The `RUSTFLAGS` the harness was built with.
+    pub rustflags: &'static str,
+    /// Whether the harness was built with AVX-512 enabled.
+    pub avx512: bool,
+    /// Whether the harness was built with GPU acceleration.
+    pub cuda: bool,
+    /// The toolchain the guest was built with, as set by `eval.sh`.
+    pub guest_toolchain: Option<String>,
+    /// The resolved `sp1-sdk` version.
+    pub sp1_sdk_version: Option<&'static str>,
+    /// The resolved `risc0-zkvm` version.
+    pub risc0_zkvm_version: Option<&'static str>,
+    /// The resolved `jolt-sdk` version.
+    pub jolt_sdk_version: Option<&'static str>,
+    /// The resolved `nexus-sdk` version.
+    pub nexus_sdk_version: Option<&'static str>,
+    /// The `SHARD_SIZE` environment variable the prover ran with.
+    pub shard_size_env: Option<String>,
+}
+
+impl Environment {
+    pub fn capture() -> Self {
+        let rustflags = env!("EVAL_RUSTFLAGS");
+        Environment {
+            git_sha: env!("VERGEN_GIT_SHA"),
+            build_timestamp: env!("VERGEN_BUILD_TIMESTAMP"),
+            cpu_model: cpu_model(),
+            cpu_cores: thread::available_parallelism().ok().map(usize::from),
+            total_memory: meminfo("MemTotal"),
+            available_memory: meminfo("MemAvailable"),
+            rustflags,
+            avx512: rustflags.contains("avx512"),
+            cuda: cfg!(feature = "cuda"),
+            guest_toolchain: env::var("GUEST_TOOLCHAIN")
+                .ok()
+                .filter(|toolchain| !toolchain.is_empty()),
+            sp1_sdk_version: option_env!("EVAL_SP1_SDK_VERSION"),
+            risc0_zkvm_version: option_env!("EVAL_RISC0_ZKVM_VERSION"),
+            jolt_sdk_version: option_env!("EVAL_JOLT_SDK_VERSION"),
+            nexus_sdk_version: option_env!("EVAL_NEXUS_SDK_VERSION"),
+            shard_size_env: env::var("SHARD_SIZE").ok(),
+        }
+    }
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_string())
+}
+
+/// Reads a field of `/proc/meminfo` in bytes.
+fn meminfo(field: &str) -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kilobytes: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
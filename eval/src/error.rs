@@ -0,0 +1,86 @@
+use std::{path::PathBuf, time::Duration};
+
+use block_manifest::ManifestError;
+use thiserror::Error;
+
+/// A failure of the evaluation harness.
+///
+/// Failures are recorded in the results as a failed run with the stage that failed, and the process
+/// exits with the code of the failure.
+#[derive(Debug, Error)]
+pub enum EvalError {
+    /// The program input is missing or could not be read.
+    #[error("missing input: {0}")]
+    MissingInput(String),
+    /// The guest ELF has not been built.
+    #[error("guest ELF not built, expected it at {}", .0.display())]
+    ElfNotBuilt(PathBuf),
+    /// The zkVM could not be set up for the program.
+    #[error("setup failed: {0}")]
+    Setup(String),
+    /// The program failed to execute, or committed an unexpected output.
+    #[error("execution failed: {0}")]
+    Execution(String),
+    /// A proving stage failed.
+    #[error("{stage} failed: {message}")]
+    Proof {
+        stage: &'static str,
+        message: String,
+    },
+    /// A proof did not verify.
+    #[error("{stage} failed: {message}")]
+    Verification {
+        stage: &'static str,
+        message: String,
+    },
+    /// A stage ran longer than the stage timeout.
+    #[error("{stage} timed out after {}s", .timeout.as_secs())]
+    Timeout {
+        stage: &'static str,
+        timeout: Duration,
+    },
+    /// The program executed more cycles than the cycle limit.
+    #[error("execution exceeded the limit of {0} cycles")]
+    CycleLimit(u64),
+}
+
+impl EvalError {
+    /// The stage that failed, named as in the stages results.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            EvalError::MissingInput(_) | EvalError::ElfNotBuilt(_) | EvalError::Setup(_) => "setup",
+            EvalError::Execution(_) | EvalError::CycleLimit(_) => "execute",
+            EvalError::Proof { stage, .. }
+            | EvalError::Verification { stage, .. }
+            | EvalError::Timeout { stage, .. } => stage,
+        }
+    }
+
+    /// The exit code of the process when the evaluation fails with this error. Codes below 3 are
+    /// used for other errors and by `clap`.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            EvalError::MissingInput(_) => 3,
+            EvalError::ElfNotBuilt(_) => 4,
+            EvalError::Setup(_) => 5,
+            EvalError::Execution(_) => 6,
+            EvalError::Proof { .. } => 7,
+            EvalError::Verification { .. } => 8,
+            EvalError::Timeout { .. } => 9,
+            EvalError::CycleLimit(_) => 10,
+        }
+    }
+
+    /// Whether an exit code is the code of an [EvalError], which the failed process has recorded in
+    /// the results itself.
+    pub fn is_exit_code(code: i32) -> bool {
+        (3..=10).contains(&code)
+    }
+}
+
+/// A block file that cannot be checked against its manifest is an unusable input.
+impl From<ManifestError> for EvalError {
+    fn from(err: ManifestError) -> Self {
+        EvalError::MissingInput(err.to_string())
+    }
+}
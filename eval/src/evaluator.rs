@@ -0,0 +1,664 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    batch,
+    error::EvalError,
+    programs::{BlockInfo, ProgramInput},
+    stats::aggregate,
+    types::Stage,
+    utils::{profile_operation, Profile},
+    EvalArgs, PerformanceReport,
+};
+
+/// The outcome of executing the program.
+pub struct Execution {
+    /// The number of cycles the execution took, `None` for zkVMs that do not count them.
+    pub cycles: Option<u64>,
+    /// The public values committed by the guest, in the zkVM's encoding.
+    pub public_values: Vec<u8>,
+    /// The cycles spent in each region the guest marks with a cycle tracker, in guest order.
+    pub regions: Vec<(String, u64)>,
+    /// The number of invocations of each syscall or precompile that was used.
+    pub syscalls: Vec<(String, u64)>,
+}
+
+/// A proof produced by one of the proving stages, along with its size in bytes if the zkVM can
+/// measure it.
+pub struct StageProof<P> {
+    pub proof: P,
+    pub size: Option<usize>,
+}
+
+/// The stages of the proving pipeline a zkVM backend implements.
+///
+/// The harness in [run] drives the selected stages in order, times each of them and assembles the
+/// [PerformanceReport]. Stages a zkVM does not support keep their default implementation, which
+/// returns `None` and leaves the corresponding report fields empty.
+///
+/// Errors are reported as a failure of the stage that returned them, unless they are an
+/// [EvalError] already. Stages that panic, as some provers do on failure, are reported the same way.
+pub trait ZkvmEvaluator: Sized {
+    /// The proof produced by the core prover.
+    type CoreProof;
+    /// The proof produced by recursive compression.
+    type CompressedProof;
+    /// The proof wrapped into a form suitable for SNARK conversion.
+    type WrapProof;
+
+    /// Whether the zkVM proves in shards of `args.shard_size`. The reports of zkVMs that do not
+    /// record a shard size of 0.
+    const SHARDED: bool = true;
+
+    /// Whether the reports label the program with its input, as in `fibonacci_1000`, or only with
+    /// its name.
+    const LABEL_INPUT: bool = true;
+
+    /// Whether `speed` is measured over the core proving and compression, as RISC0 has always
+    /// reported it, rather than over the core proving alone.
+    const SPEED_INCLUDES_COMPRESS: bool = false;
+
+    /// Loads the program and creates the executor. Executors that can stop at a cycle limit should
+    /// apply `args.max_cycles` and fail with [EvalError::CycleLimit] when it is exceeded.
+    fn setup(args: &EvalArgs) -> anyhow::Result<Self>;
+
+    /// Generates the proving and verifying keys. Only called when proving is requested.
+    fn setup_prover(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Writes the program input, encoded by the program as buffers the guest reads in order.
+    fn write_input(&mut self, input: &[Vec<u8>]) -> anyhow::Result<()>;
+
+    /// Prepares the execution (e.g. building the executor from the program and its input). Called
+    /// before every execution. Not timed.
+    fn prepare_execute(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Executes the program without proving it.
+    fn execute(&mut self) -> anyhow::Result<Execution>;
+
+    /// Generates the core proof.
+    fn prove_core(&mut self) -> anyhow::Result<StageProof<Self::CoreProof>>;
+
+    /// Returns the number of shards (or segments) of the core proof, `None` for zkVMs that do not
+    /// prove in shards.
+    fn shards(&self, _proof: &Self::CoreProof) -> anyhow::Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Verifies the core proof.
+    fn verify_core(&mut self, proof: &Self::CoreProof) -> anyhow::Result<()>;
+
+    /// Compresses the core proof with recursion.
+    fn compress(
+        &mut self,
+        _proof: Self::CoreProof,
+    ) -> anyhow::Result<Option<StageProof<Self::CompressedProof>>> {
+        Ok(None)
+    }
+
+    /// Verifies the compressed proof.
+    fn verify_compressed(&mut self, _proof: &Self::CompressedProof) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Shrinks the compressed proof before wrapping.
+    fn shrink(
+        &mut self,
+        _proof: &Self::CompressedProof,
+    ) -> anyhow::Result<Option<Self::CompressedProof>> {
+        Ok(None)
+    }
+
+    /// Verifies the shrunk proof.
+    fn verify_shrink(&mut self, _proof: &Self::CompressedProof) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Wraps the (shrunk) compressed proof for SNARK conversion.
+    fn wrap(&mut self, _proof: Self::CompressedProof) -> anyhow::Result<Option<Self::WrapProof>> {
+        Ok(None)
+    }
+
+    /// Prepares the Groth16 stage (e.g. building artifacts and warming up the prover). Not timed.
+    fn prepare_groth16(&mut self, _proof: &Self::WrapProof) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Generates the Groth16 proof and returns its size in bytes.
+    fn groth16(&mut self, _proof: &Self::WrapProof) -> anyhow::Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Prepares the PLONK stage (e.g. building artifacts and warming up the prover). Not timed.
+    fn prepare_plonk(&mut self, _proof: &Self::WrapProof) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Generates the PLONK proof and returns its size in bytes.
+    fn plonk(&mut self, _proof: &Self::WrapProof) -> anyhow::Result<Option<usize>> {
+        Ok(None)
+    }
+}
+
+/// Receives the reports of each input as soon as it has been evaluated, or why its evaluation
+/// failed.
+pub type Reporter<'a> =
+    dyn FnMut(&EvalArgs, Result<Vec<PerformanceReport>, EvalError>) -> eyre::Result<()> + 'a;
+
+/// Is told which stage is running, or that none is, so the stage timeout can be enforced. Fails
+/// once the evaluation has been abandoned.
+type StageMonitor<'a> = dyn Fn(Option<&'static str>) -> Result<(), EvalError> + 'a;
+
+/// Sets up the evaluator once and evaluates each input, the single input of the arguments or each
+/// block of a batch, passing the reports to the reporter.
+///
+/// Failures of the setup are returned, failures of an input are passed to the reporter.
+pub fn run<E: ZkvmEvaluator + 'static>(
+    args: &EvalArgs,
+    reporter: &mut Reporter,
+) -> eyre::Result<()> {
+    let inputs = batch::inputs(args)?;
+
+    if let Some(timeout) = args.stage_timeout {
+        return supervise::<E>(args, &inputs, Duration::from_secs(timeout), reporter);
+    }
+
+    let mut evaluator = setup::<E>(args)?;
+    for input_args in &inputs {
+        let reports = evaluate(&mut evaluator, input_args, &|_| Ok(()));
+        reporter(input_args, reports)?;
+    }
+
+    Ok(())
+}
+
+/// Sets up the program, the executor and the prover, which are shared by all inputs and trials.
+fn setup<E: ZkvmEvaluator>(args: &EvalArgs) -> Result<E, EvalError> {
+    let mut evaluator = E::setup(args).map_err(|err| classify(err, EvalError::Setup))?;
+    if proving_selected(args) {
+        evaluator
+            .setup_prover()
+            .map_err(|err| classify(err, EvalError::Setup))?;
+    }
+    Ok(evaluator)
+}
+
+/// What the worker evaluating the inputs tells its supervisor.
+enum Event {
+    /// A stage started, or the running stage finished when there is none.
+    Stage(Option<&'static str>),
+    /// The evaluator could not be set up.
+    SetupFailed(EvalError),
+    /// The next input was evaluated.
+    Evaluated(Result<Vec<PerformanceReport>, EvalError>),
+}
+
+/// Evaluates the inputs on a worker thread and fails the input whose stage runs longer than the
+/// timeout. A stage cannot be interrupted and would skew the measurements of the inputs after it, so
+/// the evaluation stops there and the worker is left to end with the process.
+fn supervise<E: ZkvmEvaluator + 'static>(
+    args: &EvalArgs,
+    inputs: &[EvalArgs],
+    timeout: Duration,
+    reporter: &mut Reporter,
+) -> eyre::Result<()> {
+    let (events, received) = mpsc::channel();
+    let worker_args = args.clone();
+    let worker_inputs = inputs.to_vec();
+    let worker = thread::spawn(move || {
+        // The supervisor stops listening once it has abandoned the worker.
+        let abandoned = |_| EvalError::Setup("the evaluation was abandoned".into());
+        let monitor = |stage| events.send(Event::Stage(stage)).map_err(abandoned);
+
+        let mut evaluator = match setup::<E>(&worker_args) {
+            Ok(evaluator) => evaluator,
+            Err(err) => {
+                let _ = events.send(Event::SetupFailed(err));
+                return;
+            }
+        };
+        for input_args in &worker_inputs {
+            let reports = evaluate(&mut evaluator, input_args, &monitor);
+            if events.send(Event::Evaluated(reports)).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut next = 0;
+    let mut running: Option<(&str, Instant)> = None;
+    loop {
+        let event = match running {
+            Some((stage, deadline)) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match received.recv_timeout(remaining) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        tracing::warn!("Abandoning the {stage} stage after {timeout:?}");
+                        let skipped = inputs.len() - next - 1;
+                        if skipped > 0 {
+                            tracing::warn!("Skipping the {skipped} remaining inputs");
+                        }
+                        let err = EvalError::Timeout { stage, timeout };
+                        reporter(&inputs[next], Err(err))?;
+                        return Ok(());
+                    }
+                    event => event.ok(),
+                }
+            }
+            None => received.recv().ok(),
+        };
+
+        match event {
+            Some(Event::Stage(stage)) => {
+                running = stage.map(|stage| (stage, Instant::now() + timeout));
+            }
+            Some(Event::SetupFailed(err)) => return Err(err.into()),
+            Some(Event::Evaluated(reports)) => {
+                reporter(&inputs[next], reports)?;
+                next += 1;
+            }
+            // The worker evaluated all inputs, or panicked.
+            None => break,
+        }
+    }
+
+    if let Err(payload) = worker.join() {
+        panic::resume_unwind(payload);
+    }
+    Ok(())
+}
+
+/// Runs the warm-up and measured trials on one input and aggregates the reports.
+fn evaluate<E: ZkvmEvaluator>(
+    evaluator: &mut E,
+    args: &EvalArgs,
+    monitor: &StageMonitor,
+) -> Result<Vec<PerformanceReport>, EvalError> {
+    let ProgramInput {
+        buffers,
+        expected_output,
+        block_info,
+    } = args.program.input(args, &args.prover)?;
+
+    evaluator
+        .write_input(&buffers)
+        .map_err(|err| classify(err, EvalError::Setup))?;
+
+    for warmup in 0..args.warmup {
+        tracing::info!("Running warm-up trial {}/{}", warmup + 1, args.warmup);
+        run_trial(evaluator, args, expected_output.as_deref(), monitor)?;
+    }
+
+    let trials = (0..args.trials)
+        .map(|trial| {
+            tracing::info!("Running trial {}/{}", trial + 1, args.trials);
+            Ok(PerformanceReport {
+                trials: args.trials,
+                ..run_trial(evaluator, args, expected_output.as_deref(), monitor)?
+            })
+        })
+        .collect::<Result<_, EvalError>>()?;
+
+    let mut reports = aggregate(trials);
+    if let Some(block_info) = &block_info {
+        for report in &mut reports {
+            normalise(report, block_info);
+        }
+    }
+    Ok(reports)
+}
+
+/// Fills in the measurements of a report normalised by the gas and transactions of its block, so
+/// blocks of different sizes can be compared.
+fn normalise(report: &mut PerformanceReport, block_info: &BlockInfo) {
+    report.gas_used = Some(block_info.gas_used);
+    report.transactions = Some(block_info.transactions);
+
+    if block_info.gas_used > 0 {
+        let gas_used = block_info.gas_used as f64;
+        report.cycles_per_gas = report.cycles.map(|cycles| cycles as f64 / gas_used);
+        report.prove_seconds_per_mgas = report
+            .prove_duration
+            .map(|prove_duration| prove_duration / (gas_used / 1_000_000.0));
+    }
+    if block_info.transactions > 0 {
+        report.proof_bytes_per_tx = report
+            .core_proof_size
+            .map(|size| size as f64 / block_info.transactions as f64);
+    }
+}
+
+/// Turns the error of a stage into an [EvalError], keeping it if it is one already.
+fn classify(err: anyhow::Error, fallback: impl FnOnce(String) -> EvalError) -> EvalError {
+    match err.downcast::<EvalError>() {
+        Ok(err) => err,
+        Err(err) => fallback(format!("{err:#}")),
+    }
+}
+
+/// Classifies the error of a proving stage.
+fn proof_failed(stage: &'static str) -> impl FnOnce(anyhow::Error) -> EvalError {
+    move |err| classify(err, |message| EvalError::Proof { stage, message })
+}
+
+/// Classifies the error of a verification stage.
+fn verification_failed(stage: &'static str) -> impl FnOnce(anyhow::Error) -> EvalError {
+    move |err| classify(err, |message| EvalError::Verification { stage, message })
+}
+
+fn proving_selected(args: &EvalArgs) -> bool {
+    [Stage::Core, Stage::Compress, Stage::Groth16, Stage::Plonk]
+        .iter()
+        .any(|stage| args.stages.contains(stage))
+}
+
+/// Runs the selected stages of the evaluator once and assembles the performance report.
+fn run_trial<E: ZkvmEvaluator>(
+    evaluator: &mut E,
+    args: &EvalArgs,
+    expected_output: Option<&[u8]>,
+    monitor: &StageMonitor,
+) -> Result<PerformanceReport, EvalError> {
+    let selected = |stages: &[Stage]| stages.iter().any(|stage| args.stages.contains(stage));
+
+    // Execute the program.
+    evaluator
+        .prepare_execute()
+        .map_err(|err| classify(err, EvalError::Execution))?;
+    let (execution, execution_profile) = limit(monitor, "execute", || evaluator.execute())?;
+    let execution = execution.map_err(|err| classify(err, EvalError::Execution))?;
+    let cycles = execution.cycles;
+
+    // Enforce the cycle limit for the zkVMs that cannot stop at it.
+    check_cycle_limit(cycles, args.max_cycles)?;
+
+    // Check the public output against the one computed on the host.
+    if let Some(expected_output) = expected_output {
+        if execution.public_values != expected_output {
+            return Err(EvalError::Execution(format!(
+                "public output mismatch: expected 0x{}, got 0x{}",
+                hex::encode(expected_output),
+                hex::encode(&execution.public_values)
+            )));
+        }
+        tracing::info!("Public output matches: 0x{}", hex::encode(expected_output));
+    }
+
+    let mut report = PerformanceReport {
+        program: if E::LABEL_INPUT {
+            args.program.label(args)
+        } else {
+            args.program.name().to_string()
+        },
+        prover: args.prover.to_string(),
+        shard_size: if E::SHARDED { args.shard_size } else { 0 },
+        cycles,
+        execution_duration: execution_profile.duration.as_secs_f64(),
+        regions: execution.regions,
+        syscalls: execution.syscalls,
+        ..Default::default()
+    };
+    record(&mut report, "execute", execution_profile);
+
+    if !proving_selected(args) {
+        return Ok(report);
+    }
+
+    // Generate and verify the core proof.
+    let (core_proof, profile) = limit(monitor, "core_prove", || evaluator.prove_core())?;
+    let core_proof = core_proof.map_err(proof_failed("core_prove"))?;
+    let core_prove_duration = record(&mut report, "core_prove", profile);
+    let (verified, profile) = limit(monitor, "core_verify", || {
+        evaluator.verify_core(&core_proof.proof)
+    })?;
+    verified.map_err(verification_failed("core_verify"))?;
+    let core_verify_duration = record(&mut report, "core_verify", profile);
+
+    // The speeds are left empty for zkVMs that do not count cycles.
+    let speed = |duration: Duration| cycles.map(|cycles| cycles as f64 / duration.as_secs_f64());
+    let core_khz = speed(core_prove_duration).map(|speed| speed / 1_000.0);
+    let shards = evaluator.shards(&core_proof.proof);
+    report.shards = shards.map_err(proof_failed("core_prove"))?;
+    report.speed = speed(core_prove_duration);
+    report.prove_duration = Some(core_prove_duration.as_secs_f64());
+    report.core_prove_duration = Some(core_prove_duration.as_secs_f64());
+    report.core_verify_duration = Some(core_verify_duration.as_secs_f64());
+    report.core_proof_size = core_proof.size;
+    report.core_khz = core_khz;
+    report.overall_khz = core_khz;
+
+    if !selected(&[Stage::Compress, Stage::Groth16, Stage::Plonk]) {
+        return Ok(report);
+    }
+
+    // Compress the core proof with recursion.
+    let (compressed_proof, profile) = limit(monitor, "compress_prove", || {
+        evaluator.compress(core_proof.proof)
+    })?;
+    let Some(compressed_proof) = compressed_proof.map_err(proof_failed("compress_prove"))? else {
+        return Ok(report);
+    };
+    let compress_duration = record(&mut report, "compress_prove", profile);
+    if let Some(size) = compressed_proof.size {
+        tracing::info!("Recursive proof size: {size}");
+    }
+
+    let (verified, profile) = limit(monitor, "compress_verify", || {
+        evaluator.verify_compressed(&compressed_proof.proof)
+    })?;
+    verified.map_err(verification_failed("compress_verify"))?;
+    let compress_verify_duration = record(&mut report, "compress_verify", profile);
+
+    let prove_duration = core_prove_duration + compress_duration;
+    report.prove_duration = Some(prove_duration.as_secs_f64());
+    report.compress_prove_duration = Some(compress_duration.as_secs_f64());
+    report.compress_verify_duration = Some(compress_verify_duration.as_secs_f64());
+    report.compress_proof_size = compressed_proof.size;
+    if E::SPEED_INCLUDES_COMPRESS {
+        report.speed = speed(prove_duration);
+    }
+    report.overall_khz = speed(prove_duration).map(|speed| speed / 1_000.0);
+
+    if !selected(&[Stage::Groth16, Stage::Plonk]) {
+        return Ok(report);
+    }
+
+    // Shrink the compressed proof, if the zkVM has a shrink stage.
+    let (shrunk_proof, profile) = limit(monitor, "shrink_prove", || {
+        evaluator.shrink(&compressed_proof.proof)
+    })?;
+    let to_wrap = match shrunk_proof.map_err(proof_failed("shrink_prove"))? {
+        Some(shrunk_proof) => {
+            let shrink_prove_duration = record(&mut report, "shrink_prove", profile);
+            report.shrink_prove_duration = Some(shrink_prove_duration.as_secs_f64());
+            let (verified, _) = limit(monitor, "shrink_verify", || {
+                evaluator.verify_shrink(&shrunk_proof)
+            })?;
+            verified.map_err(verification_failed("shrink_verify"))?;
+            shrunk_proof
+        }
+        None => compressed_proof.proof,
+    };
+
+    // Wrap the proof and convert it to SNARKs.
+    let (wrap_proof, profile) = limit(monitor, "wrap_prove", || evaluator.wrap(to_wrap))?;
+    let Some(wrap_proof) = wrap_proof.map_err(proof_failed("wrap_prove"))? else {
+        return Ok(report);
+    };
+    let wrap_prove_duration = record(&mut report, "wrap_prove", profile);
+    report.wrap_prove_duration = Some(wrap_prove_duration.as_secs_f64());
+
+    if selected(&[Stage::Groth16]) {
+        // The preparation is not timed, but it is limited so that a failed build fails the stage.
+        let (prepared, _) = limit(monitor, "groth16_prove", || {
+            evaluator.prepare_groth16(&wrap_proof)
+        })?;
+        prepared.map_err(proof_failed("groth16_prove"))?;
+        let (groth16_proof_size, profile) =
+            limit(monitor, "groth16_prove", || evaluator.groth16(&wrap_proof))?;
+        let groth16_proof_size = groth16_proof_size.map_err(proof_failed("groth16_prove"))?;
+        if groth16_proof_size.is_some() {
+            let groth16_prove_duration = record(&mut report, "groth16_prove", profile);
+            report.groth16_prove_duration = Some(groth16_prove_duration.as_secs_f64());
+            report.groth16_proof_size = groth16_proof_size;
+        }
+    }
+
+    if selected(&[Stage::Plonk]) {
+        let (prepared, _) = limit(monitor, "plonk_prove", || {
+            evaluator.prepare_plonk(&wrap_proof)
+        })?;
+        prepared.map_err(proof_failed("plonk_prove"))?;
+        let (plonk_proof_size, profile) =
+            limit(monitor, "plonk_prove", || evaluator.plonk(&wrap_proof))?;
+        let plonk_proof_size = plonk_proof_size.map_err(proof_failed("plonk_prove"))?;
+        if plonk_proof_size.is_some() {
+            let plonk_prove_duration = record(&mut report, "plonk_prove", profile);
+            report.plonk_prove_duration = Some(plonk_prove_duration.as_secs_f64());
+            report.plonk_proof_size = plonk_proof_size;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Profiles a stage, telling the monitor while it runs so it can be timed out. A panic of the stage
+/// is returned as its error.
+fn limit<T>(
+    monitor: &StageMonitor,
+    stage: &'static str,
+    operation: impl FnOnce() -> anyhow::Result<T>,
+) -> Result<(anyhow::Result<T>, Profile), EvalError> {
+    monitor(Some(stage))?;
+    // The panic is caught inside the profiled operation, which would otherwise never finish.
+    let result = profile_operation(|| {
+        panic::catch_unwind(AssertUnwindSafe(operation)).unwrap_or_else(|payload| {
+            Err(anyhow::anyhow!("panicked: {}", panic_message(&*payload)))
+        })
+    });
+    monitor(None)?;
+    Ok(result)
+}
+
+/// The message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// Fails with [EvalError::CycleLimit] if the execution took more cycles than the limit.
+fn check_cycle_limit(cycles: Option<u64>, max_cycles: Option<u64>) -> Result<(), EvalError> {
+    match (cycles, max_cycles) {
+        (Some(cycles), Some(max_cycles)) if cycles > max_cycles => {
+            Err(EvalError::CycleLimit(max_cycles))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Records the resource usage of a stage that ran and returns its duration.
+fn record(report: &mut PerformanceReport, stage: &'static str, profile: Profile) -> Duration {
+    report.peak_rss = report.peak_rss.max(profile.peak_rss);
+    let duration = profile.duration;
+    report.stage_profiles.push((stage, profile));
+
+    // The CPU time of all stages so far, and the cores they kept busy on average.
+    let profiles = || report.stage_profiles.iter().map(|(_, profile)| profile);
+    let user_time = profiles()
+        .map(|profile| profile.user_time)
+        .sum::<Option<f64>>();
+    let system_time = profiles()
+        .map(|profile| profile.system_time)
+        .sum::<Option<f64>>();
+    let total_duration: f64 = profiles()
+        .map(|profile| profile.duration.as_secs_f64())
+        .sum();
+    report.cpu_utilisation = user_time
+        .zip(system_time)
+        .filter(|_| total_duration > 0.0)
+        .map(|(user_time, system_time)| (user_time + system_time) / total_duration);
+    report.user_time = user_time;
+    report.system_time = system_time;
+    duration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_the_cycle_limit() {
+        assert!(check_cycle_limit(Some(1_000), None).is_ok());
+        assert!(check_cycle_limit(Some(1_000), Some(1_000)).is_ok());
+        assert!(matches!(
+            check_cycle_limit(Some(1_001), Some(1_000)),
+            Err(EvalError::CycleLimit(1_000))
+        ));
+        // zkVMs that do not count cycles cannot be held to the limit.
+        assert!(check_cycle_limit(None, Some(1_000)).is_ok());
+    }
+
+    #[test]
+    fn returns_the_panic_of_a_stage_as_its_error() {
+        let (result, _) = limit(&|_| Ok(()), "groth16_prove", || -> anyhow::Result<()> {
+            panic!("artifacts not found")
+        })
+        .unwrap();
+        let err = result.map_err(proof_failed("groth16_prove")).unwrap_err();
+        assert_eq!(err.stage(), "groth16_prove");
+        assert_eq!(
+            err.to_string(),
+            "groth16_prove failed: panicked: artifacts not found"
+        );
+
+        let (result, _) = limit(&|_| Ok(()), "execute", || -> anyhow::Result<()> {
+            panic!("{} cycles", 10)
+        })
+        .unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "panicked: 10 cycles");
+    }
+
+    #[test]
+    fn normalises_by_the_block() {
+        let block_info = BlockInfo {
+            number: 7,
+            chain: "taiko_test".to_string(),
+            gas_used: 2_000_000,
+            transactions: 4,
+            accounts: 10,
+            storage_slots: 20,
+            blob_proof_type: None,
+        };
+        let mut report = PerformanceReport {
+            cycles: Some(6_000_000),
+            prove_duration: Some(10.0),
+            core_proof_size: Some(1_000),
+            ..Default::default()
+        };
+        normalise(&mut report, &block_info);
+        assert_eq!(report.cycles_per_gas, Some(3.0));
+        assert_eq!(report.prove_seconds_per_mgas, Some(5.0));
+        assert_eq!(report.proof_bytes_per_tx, Some(250.0));
+
+        // Measurements the zkVM cannot make stay empty rather than 0.
+        let mut report = PerformanceReport::default();
+        normalise(&mut report, &block_info);
+        assert_eq!(report.gas_used, Some(2_000_000));
+        assert_eq!(report.cycles_per_gas, None);
+        assert_eq!(report.prove_seconds_per_mgas, None);
+        assert_eq!(report.proof_bytes_per_tx, None);
+    }
+}
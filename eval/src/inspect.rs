@@ -0,0 +1,55 @@
+use std::{fs, path::Path};
+
+use block_manifest::{sha256, BlockFormat};
+use eyre::{bail, Result, WrapErr};
+use rsp_client_executor::io::ClientExecutorInput;
+
+use crate::{block_format, programs::BlockInfo};
+
+/// Prints what a downloaded block holds: a `raiko` `GuestInput` in any [BlockFormat] or a `reth`
+/// `ClientExecutorInput` in bincode, told apart by the file extension.
+pub fn inspect(path: &Path) -> Result<()> {
+    let bytes = fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let (info, json_size, bincode) = if let Some(format) = BlockFormat::from_path(path) {
+        let input = block_format::decode(format, &bytes)?;
+        let info = BlockInfo::from_guest_input(&input);
+        (
+            info,
+            serde_json::to_vec(&input)?.len(),
+            bincode::serialize(&input)?,
+        )
+    } else if path.extension().is_some_and(|ext| ext == "bin") {
+        let input: ClientExecutorInput = bincode::deserialize(&bytes)?;
+        let info = BlockInfo::from_client_input(&input);
+        (
+            info,
+            serde_json::to_vec(&input)?.len(),
+            bincode::serialize(&input)?,
+        )
+    } else {
+        bail!(
+            "Unknown block file {}, expected a .json, .bincode or .bincode.zst GuestInput or a \
+             .bin ClientExecutorInput",
+            path.display()
+        )
+    };
+    let zstd_size = zstd::encode_all(&bincode[..], zstd::DEFAULT_COMPRESSION_LEVEL)?.len();
+
+    println!("{}", path.display());
+    println!("  block:           {}", info.number);
+    println!("  chain:           {}", info.chain);
+    println!("  transactions:    {}", info.transactions);
+    println!("  gas used:        {}", info.gas_used);
+    println!("  accounts:        {}", info.accounts);
+    println!("  storage slots:   {}", info.storage_slots);
+    if let Some(blob_proof_type) = &info.blob_proof_type {
+        println!("  blob proof type: {blob_proof_type}");
+    }
+    println!("  file size:       {} bytes", bytes.len());
+    println!("  SHA-256:         {}", sha256(&bytes));
+    println!("  JSON size:       {json_size} bytes");
+    println!("  bincode size:    {} bytes", bincode.len());
+    println!("  zstd size:       {zstd_size} bytes");
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 #[cfg(feature = "jolt")]
+use anyhow::{bail, Result};
+#[cfg(feature = "jolt")]
 use jolt_sdk::{
-    host::Program, Jolt, JoltHyperKZGProof, JoltPreprocessing, RV32IJoltVM, Serializable,
+    host::Program, Jolt, JoltHyperKZGProof, JoltPreprocessing, ProofTranscript, RV32IJoltVM,
+    Serializable, F, PCS,
 };
 
 #[cfg(feature = "jolt")]
@@ -19,101 +22,160 @@ use tendermint_j::{
 };
 
 #[cfg(feature = "jolt")]
-use crate::{utils::time_operation, ProgramId};
+use crate::evaluator::{self, Execution, StageProof, ZkvmEvaluator};
+
+use crate::{error::EvalError, evaluator::Reporter, EvalArgs};
+
+#[cfg(feature = "jolt")]
+type Preprocessing = JoltPreprocessing<4, F, PCS, ProofTranscript>;
 
-use crate::{EvalArgs, PerformanceReport};
+/// The programs with a Jolt guest. Jolt links the guest programs into the host, so there is no ELF
+/// to load and the programs are dispatched by name.
+#[cfg(feature = "jolt")]
+#[derive(Clone, Copy)]
+enum JoltProgram {
+    Fibonacci,
+    Loop,
+    Tendermint,
+}
 
+#[cfg(feature = "jolt")]
+pub struct JoltEvaluator {
+    program: JoltProgram,
+    input: Option<u32>,
+    cycles: u64,
+    preprocessed: Option<(Program, Preprocessing)>,
+    proof: Option<JoltHyperKZGProof>,
+}
+
+#[cfg(not(feature = "jolt"))]
 pub struct JoltEvaluator;
 
-impl JoltEvaluator {
-    #[cfg(feature = "jolt")]
-    pub fn eval(args: &EvalArgs) -> PerformanceReport {
-        let (analyze, preprocess, prove): (
-            Box<dyn Fn() -> _>,
-            fn() -> (Program, JoltPreprocessing<4, _, _, _>),
-            Box<dyn Fn(Program, JoltPreprocessing<4, _, _, _>) -> ((), JoltHyperKZGProof)>,
-        ) = match args.program {
-            ProgramId::Fibonacci => {
-                let analyze = Box::new(|| {
-                    analyze_fibonacci(args.fibonacci_input.expect("missing fibonacci input"))
-                });
-                let prove = Box::new(|program, preprocessing| {
-                    prove_fibonacci(
-                        program,
-                        preprocessing,
-                        args.fibonacci_input.expect("missing fibonacci input"),
-                    )
-                });
-                (analyze, preprocess_fibonacci, prove)
-            }
-            ProgramId::Loop => {
-                let analyze = Box::new(|| analyze_loop());
-                let prove = Box::new(|program, preprocessing| prove_loop(program, preprocessing));
-                (analyze, preprocess_loop, prove)
-            }
-            ProgramId::Tendermint => {
-                let analyze = Box::new(|| analyze_tendermint());
-                let prove =
-                    Box::new(|program, preprocessing| prove_tendermint(program, preprocessing));
-                (analyze, preprocess_tendermint, prove)
-            }
-            _ => panic!("not implemented yet"),
+#[cfg(feature = "jolt")]
+impl ZkvmEvaluator for JoltEvaluator {
+    // Jolt verification consumes the proof, so the evaluator keeps it until then.
+    type CoreProof = ();
+    type CompressedProof = ();
+    type WrapProof = ();
+
+    const SHARDED: bool = false;
+    const LABEL_INPUT: bool = false;
+
+    fn setup(args: &EvalArgs) -> Result<Self> {
+        let program = match args.program.name() {
+            "fibonacci" => JoltProgram::Fibonacci,
+            "loop" => JoltProgram::Loop,
+            "tendermint" => JoltProgram::Tendermint,
+            name => bail!("Jolt does not support the {name} program yet"),
         };
 
-        // Get the total cycles of the program
-        let summary = analyze();
-        let instruction_count = summary.analyze::<jolt_sdk::F>();
-        let total_cycles = instruction_count
+        Ok(JoltEvaluator {
+            program,
+            input: None,
+            cycles: 0,
+            preprocessed: None,
+            proof: None,
+        })
+    }
+
+    fn write_input(&mut self, input: &[Vec<u8>]) -> Result<()> {
+        // The only typed input the guests take is the fibonacci `u32`.
+        self.input = input
+            .first()
+            .map(|buffer| bincode::deserialize(buffer))
+            .transpose()?;
+
+        // Get the total cycles of the program. Tracing is not part of the execution time.
+        let summary = match self.program {
+            JoltProgram::Fibonacci => analyze_fibonacci(self.fibonacci_input()?),
+            JoltProgram::Loop => analyze_loop(),
+            JoltProgram::Tendermint => analyze_tendermint(),
+        };
+        let instruction_count = summary.analyze::<F>();
+        self.cycles = instruction_count
             .iter()
             .map(|(_, count)| count)
-            .sum::<usize>();
+            .sum::<usize>() as u64;
+        Ok(())
+    }
 
+    /// Jolt reports the preprocessing of the program as its execution.
+    fn execute(&mut self) -> Result<Execution> {
         // Generate the program and arithmetization
-        let ((program, preprocessing), execution_duration) = time_operation(|| preprocess());
+        self.preprocessed = Some(match self.program {
+            JoltProgram::Fibonacci => preprocess_fibonacci(),
+            JoltProgram::Loop => preprocess_loop(),
+            JoltProgram::Tendermint => preprocess_tendermint(),
+        });
+
+        // The Jolt guests do not commit any public values.
+        Ok(Execution {
+            cycles: Some(self.cycles),
+            public_values: Vec::new(),
+            regions: Vec::new(),
+            syscalls: Vec::new(),
+        })
+    }
+
+    fn prove_core(&mut self) -> Result<StageProof<Self::CoreProof>> {
+        let (program, preprocessing) = self.preprocessed()?;
 
         // Generate the proof
-        let ((_, proof), prove_duration) =
-            time_operation(|| prove(program.clone(), preprocessing.clone()));
+        let (_, proof) = match self.program {
+            JoltProgram::Fibonacci => {
+                prove_fibonacci(program, preprocessing, self.fibonacci_input()?)
+            }
+            JoltProgram::Loop => prove_loop(program, preprocessing),
+            JoltProgram::Tendermint => prove_tendermint(program, preprocessing),
+        };
 
         // Get the proof size
-        let proof_size = proof.size().expect("failed to get proof size");
+        let size = proof.size()?;
+        self.proof = Some(proof);
+        Ok(StageProof {
+            proof: (),
+            size: Some(size),
+        })
+    }
 
-        // Verify the proof
-        let (_, verify_duration) = time_operation(|| {
-            RV32IJoltVM::verify(preprocessing, proof.proof, proof.commitments, None)
-        });
+    fn verify_core(&mut self, _proof: &Self::CoreProof) -> Result<()> {
+        let proof = self
+            .proof
+            .take()
+            .ok_or_else(|| EvalError::Setup("program must be proven before verifying".into()))?;
+        let (_, preprocessing) = self.preprocessed()?;
+        RV32IJoltVM::verify(preprocessing, proof.proof, proof.commitments, None)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jolt")]
+impl JoltEvaluator {
+    fn fibonacci_input(&self) -> Result<u32> {
+        let input = self
+            .input
+            .ok_or_else(|| EvalError::MissingInput("fibonacci_input not provided".into()))?;
+        Ok(input)
+    }
 
-        let core_khz = total_cycles as f64 / prove_duration.as_secs_f64() / 1_000.0;
-        let overall_khz = total_cycles as f64 / prove_duration.as_secs_f64() / 1_000.0;
-
-        PerformanceReport {
-            program: args.program.to_string(),
-            prover: args.prover.to_string(),
-            shard_size: 0,
-            shards: 0,
-            cycles: total_cycles as u64,
-            speed: (total_cycles as f64) / prove_duration.as_secs_f64(),
-            execution_duration: execution_duration.as_secs_f64(),
-            prove_duration: prove_duration.as_secs_f64(),
-            core_prove_duration: prove_duration.as_secs_f64(),
-            core_verify_duration: verify_duration.as_secs_f64(),
-            core_proof_size: proof_size,
-            compress_prove_duration: 0.0,
-            compress_verify_duration: 0.0,
-            compress_proof_size: 0,
-            core_khz,
-            overall_khz,
-            shrink_prove_duration: 0.0,
-            wrap_prove_duration: 0.0,
-            groth16_prove_duration: 0.0,
-            groth16_proof_size: 0,
-            plonk_prove_duration: 0.0,
-            plonk_proof_size: 0,
-        }
+    fn preprocessed(&self) -> Result<(Program, Preprocessing), EvalError> {
+        self.preprocessed
+            .clone()
+            .ok_or_else(|| EvalError::Setup("program must be executed before proving".into()))
+    }
+}
+
+impl JoltEvaluator {
+    #[cfg(feature = "jolt")]
+    pub fn eval(args: &EvalArgs, reporter: &mut Reporter) -> eyre::Result<()> {
+        evaluator::run::<Self>(args, reporter)
     }
 
     #[cfg(not(feature = "jolt"))]
-    pub fn eval(_args: &EvalArgs) -> PerformanceReport {
-        panic!("Jolt feature is not enabled. Please compile with --features jolt")
+    pub fn eval(_args: &EvalArgs, _reporter: &mut Reporter) -> eyre::Result<()> {
+        Err(EvalError::Setup(
+            "Jolt feature is not enabled. Please compile with --features jolt".into(),
+        )
+        .into())
     }
 }
@@ -1,31 +1,107 @@
+mod batch;
+mod block_format;
+mod environment;
+mod error;
+mod evaluator;
+mod inspect;
 mod jolt;
+mod native;
 mod nexus;
+mod output;
+mod programs;
 mod risc0;
 mod sp1;
+mod stats;
+mod sweep;
 mod types;
 mod utils;
 
-use std::{
-    fs::{create_dir_all, OpenOptions},
-    path::PathBuf,
-};
+use std::{ops::RangeInclusive, path::PathBuf, process::ExitCode};
 
-use clap::Parser;
-use csv::WriterBuilder;
+use batch::BatchSummary;
+use block_manifest::BlockFormat;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use error::EvalError;
 use eyre::Result;
 use jolt::JoltEvaluator;
+use native::NativeEvaluator;
 use nexus::NexusEvaluator;
+use output::{csv_header, migrate_csv, write_results, RunMetadata};
+use programs::BenchmarkProgram;
 use serde::Serialize;
-use types::{ProgramId, ProverId};
+use stats::Statistic;
+use types::{OutputFormat, ProverId, Stage};
+use utils::Profile;
 
 use risc0::Risc0Evaluator;
 use sp1::SP1Evaluator;
 
-#[derive(Parser, Clone)]
-#[command(about = "Evaluate the performance of a zkVM on a program.")]
+#[derive(Parser)]
+#[command(
+    about = "Evaluate the performance of a zkVM on a program.",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    eval: Option<EvalArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Rewrite result CSVs written by older versions with the current report columns.
+    ///
+    /// Missing columns are left empty and columns that are no longer reported, such as the
+    /// `hashfn` column written by `eval_lita.sh`, are dropped.
+    Migrate {
+        /// The CSV files to migrate in place.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Evaluate every combination of a matrix of programs, provers, shard sizes and inputs.
+    ///
+    /// Each guest is built once and each combination is evaluated in a child process, so the
+    /// binary must be compiled with the features of all provers in the matrix.
+    ///
+    /// Completed combinations are recorded in a state file next to the results and skipped when
+    /// the sweep is restarted. Failed combinations are recorded as rows with an error.
+    Sweep {
+        /// The TOML or JSON file describing the matrix.
+        matrix: PathBuf,
+        /// Run the combinations that failed in a previous sweep again.
+        #[arg(long)]
+        retry_failed: bool,
+    },
+    /// Print the block number, chain, transactions, gas used, state witness and serialized sizes
+    /// of downloaded blocks.
+    Inspect {
+        /// The GuestInput (`.json`, `.bincode` or `.bincode.zst`) or `.bin` ClientExecutorInput
+        /// files to inspect.
+        #[arg(required = true)]
+        blocks: Vec<PathBuf>,
+    },
+    /// Convert the Taiko blocks of `eval/blocks-taiko_*` directories to another storage format.
+    ///
+    /// The format of a block is told by its extension, so `eval` reads converted blocks without
+    /// further options.
+    Convert {
+        /// The block directories to convert.
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+        /// The format to write the blocks in.
+        #[arg(long, value_enum, default_value = "zstd")]
+        to: BlockFormat,
+        /// Remove the files the blocks were converted from.
+        #[arg(long)]
+        remove_source: bool,
+    },
+}
+
+#[derive(Args, Clone)]
 pub struct EvalArgs {
-    #[arg(long)]
-    program: ProgramId,
+    #[arg(long, value_parser = programs::parse_program)]
+    program: &'static dyn BenchmarkProgram,
     #[arg(long)]
     prover: ProverId,
     // #[arg(long)]
@@ -36,14 +112,56 @@ pub struct EvalArgs {
     filename: String,
     #[arg(long)]
     block_name: Option<String>,
+    /// The directory to read blocks from instead of the default one of the program. Without
+    /// `--block-name`, every block in it is evaluated with one setup, writing a row per block and a
+    /// summary to `<filename>_summary`.
+    #[arg(long)]
+    blocks_dir: Option<PathBuf>,
+    /// Evaluate the blocks numbered `<first>..<last>` (inclusive) from the blocks directory with
+    /// one setup, like `--blocks-dir`.
+    #[arg(long, value_parser = batch::parse_block_range, conflicts_with = "block_name")]
+    block_range: Option<RangeInclusive<u64>>,
     #[arg(long)]
     fibonacci_input: Option<u32>,
     #[arg(long)]
     taiko_blocks_dir_suffix: Option<String>,
+    /// The stages to run. The program is always executed and the stages a selected stage
+    /// depends on are run as well.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values = ["execute", "core", "compress", "groth16", "plonk"]
+    )]
+    stages: Vec<Stage>,
+    /// The number of measured trials. Setup is shared across trials.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    trials: u32,
+    /// The number of unmeasured trials run before the measured ones.
+    #[arg(long, default_value_t = 0)]
+    warmup: u32,
+    /// The format of the results file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+    /// The maximum number of cycles the program may execute. Unlimited by default.
+    #[arg(long)]
+    max_cycles: Option<u64>,
+    /// The wall-clock timeout of each stage in seconds. A stage that runs longer fails its input
+    /// as timed out and ends the evaluation, skipping the remaining blocks. Unlimited by default.
+    #[arg(long)]
+    stage_timeout: Option<u64>,
+}
+
+impl EvalArgs {
+    /// Whether a batch of blocks is evaluated rather than a single input.
+    fn is_batch(&self) -> bool {
+        self.block_name.is_none() && (self.blocks_dir.is_some() || self.block_range.is_some())
+    }
 }
 
 /// The performance report of a zkVM on a program.
-#[derive(Debug, Serialize, Default)]
+///
+/// Fields of stages that were not run, or that the zkVM does not support, are left empty.
+#[derive(Debug, Serialize, Default, Clone)]
 pub struct PerformanceReport {
     /// The program that is being evaluated.
     pub program: String,
@@ -51,51 +169,226 @@ pub struct PerformanceReport {
     pub prover: String,
     /// The shard size that is being evaluated.
     pub shard_size: u64,
-    /// The number of shards.
-    pub shards: usize,
-    /// The reported number of cycles.
+    /// The number of measured trials.
+    pub trials: u32,
+    /// The statistic the measurements are aggregated with, empty for a single trial.
+    pub statistic: Option<Statistic>,
+    /// The number of shards, empty for zkVMs that do not prove in shards.
+    pub shards: Option<usize>,
+    /// The reported number of cycles, empty for zkVMs that do not count them.
     ///
     /// Note that this number may vary based on the zkVM.
-    pub cycles: u64,
-    /// The reported speed in cycles per second.
-    pub speed: f64,
+    pub cycles: Option<u64>,
+    /// The gas used by the block, for programs that execute a block.
+    pub gas_used: Option<u64>,
+    /// The number of transactions in the block.
+    pub transactions: Option<usize>,
+    /// The number of cycles per unit of gas.
+    pub cycles_per_gas: Option<f64>,
+    /// The proving duration in seconds per million gas.
+    pub prove_seconds_per_mgas: Option<f64>,
+    /// The size of the core proof in bytes per transaction.
+    pub proof_bytes_per_tx: Option<f64>,
+    /// The reported speed in cycles per second, over the core proving time (and the compression
+    /// time for RISC0).
+    pub speed: Option<f64>,
     /// The reported duration of the execution in seconds.
     pub execution_duration: f64,
     /// The reported duration of the prover in seconds.
-    pub prove_duration: f64,
+    pub prove_duration: Option<f64>,
     /// The reported duration of the core proving time in seconds.
-    pub core_prove_duration: f64,
+    pub core_prove_duration: Option<f64>,
     /// The reported duration of the verifier in seconds.
-    pub core_verify_duration: f64,
-    /// The size of the core proof.
-    pub core_proof_size: usize,
+    pub core_verify_duration: Option<f64>,
+    /// The size of the core proof in bytes, empty if the zkVM cannot measure it.
+    pub core_proof_size: Option<usize>,
     /// The reported duration of the recursive proving time in seconds.
-    pub compress_prove_duration: f64,
+    pub compress_prove_duration: Option<f64>,
     /// The reported duration of the verifier in seconds.
-    pub compress_verify_duration: f64,
+    pub compress_verify_duration: Option<f64>,
     /// The size of the recursive proof in bytes.
-    pub compress_proof_size: usize,
+    pub compress_proof_size: Option<usize>,
     /// The speed of the core proving time in KHz.
-    pub core_khz: f64,
+    pub core_khz: Option<f64>,
     /// The overall speed in KHz.
-    pub overall_khz: f64,
+    pub overall_khz: Option<f64>,
     /// The reported duration of the shrink proving time in seconds.
-    pub shrink_prove_duration: f64,
+    pub shrink_prove_duration: Option<f64>,
     /// The reported duration of the wrap proving time in seconds.
-    pub wrap_prove_duration: f64,
+    pub wrap_prove_duration: Option<f64>,
     /// The reported duration of the groth16 proving time in seconds.
-    pub groth16_prove_duration: f64,
+    pub groth16_prove_duration: Option<f64>,
     /// The size of the groth16 proof in bytes.
-    pub groth16_proof_size: usize,
+    pub groth16_proof_size: Option<usize>,
     /// The reported duration of the PLONK proving time in seconds.
-    pub plonk_prove_duration: f64,
+    pub plonk_prove_duration: Option<f64>,
     /// The size of the PLONK proof in bytes.
-    pub plonk_proof_size: usize,
+    pub plonk_proof_size: Option<usize>,
+    /// The peak resident set size in bytes over all stages that were run.
+    pub peak_rss: Option<u64>,
+    /// The average number of cores kept busy over all stages that were run.
+    pub cpu_utilisation: Option<f64>,
+    /// The CPU time spent in user mode in seconds over all stages that were run.
+    pub user_time: Option<f64>,
+    /// The CPU time spent in kernel mode in seconds over all stages that were run.
+    pub system_time: Option<f64>,
+    /// Why the run failed, empty if it succeeded.
+    pub error: Option<String>,
+    /// The stage the run failed in, as in the stages results, if the harness could tell.
+    pub failed_stage: Option<&'static str>,
+    /// The duration and resource usage of each stage that was run, written to the stages CSV.
+    #[serde(skip)]
+    pub stage_profiles: Vec<(&'static str, Profile)>,
+    /// The cycles spent in each cycle tracker region of the guest, written to the breakdown CSV.
+    #[serde(skip)]
+    pub regions: Vec<(String, u64)>,
+    /// The number of invocations of each syscall or precompile, written to the syscalls CSV.
+    #[serde(skip)]
+    pub syscalls: Vec<(String, u64)>,
+}
+
+impl PerformanceReport {
+    /// The report of a run that failed, recorded in place of its results.
+    pub fn failure(args: &EvalArgs, error: String, failed_stage: Option<&'static str>) -> Self {
+        PerformanceReport {
+            program: args.program.label(args),
+            prover: args.prover.to_string(),
+            shard_size: args.shard_size,
+            trials: args.trials,
+            error: Some(error),
+            failed_stage,
+            ..Default::default()
+        }
+    }
+}
+
+/// The cycles spent in one cycle tracker region of the guest.
+#[derive(Debug, Serialize)]
+pub struct CycleRegionReport {
+    /// The program that is being evaluated.
+    pub program: String,
+    /// The block the program was run on, if any.
+    pub block: Option<String>,
+    /// The prover that is being evaluated.
+    pub prover: String,
+    /// The statistic of the report the row belongs to, empty for a single trial.
+    pub statistic: Option<Statistic>,
+    /// The name of the region.
+    pub region: String,
+    /// The number of cycles spent in the region.
+    pub cycles: u64,
+}
+
+/// The duration and resource usage of one stage.
+#[derive(Debug, Serialize)]
+pub struct StageProfileReport {
+    /// The program that is being evaluated.
+    pub program: String,
+    /// The block the program was run on, if any.
+    pub block: Option<String>,
+    /// The prover that is being evaluated.
+    pub prover: String,
+    /// The statistic of the report the row belongs to, empty for a single trial.
+    pub statistic: Option<Statistic>,
+    /// The name of the stage, as in the report columns.
+    pub stage: &'static str,
+    /// The duration of the stage in seconds.
+    pub duration: f64,
+    /// The peak resident set size in bytes, sampled while the stage ran.
+    pub peak_rss: Option<u64>,
+    /// The average number of cores the stage kept busy.
+    pub cpu_utilisation: Option<f64>,
+    /// The CPU time spent in user mode in seconds.
+    pub user_time: Option<f64>,
+    /// The CPU time spent in kernel mode in seconds.
+    pub system_time: Option<f64>,
 }
 
-fn main() -> Result<()> {
-    let args = EvalArgs::parse();
+/// The number of invocations of one syscall or precompile by the guest.
+#[derive(Debug, Serialize)]
+pub struct SyscallReport {
+    /// The program that is being evaluated.
+    pub program: String,
+    /// The block the program was run on, if any.
+    pub block: Option<String>,
+    /// The prover that is being evaluated.
+    pub prover: String,
+    /// The statistic of the report the row belongs to, empty for a single trial.
+    pub statistic: Option<Statistic>,
+    /// The name of the syscall or precompile, as the zkVM reports it.
+    pub syscall: String,
+    /// The number of invocations.
+    pub count: u64,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            // Failures of the harness have their own exit codes, so a sweep can tell them apart.
+            let code = err
+                .downcast_ref::<EvalError>()
+                .map_or(1, EvalError::exit_code);
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Migrate { files }) => migrate(&files),
+        Some(Command::Sweep {
+            matrix,
+            retry_failed,
+        }) => sweep::sweep(&matrix, retry_failed),
+        Some(Command::Inspect { blocks }) => {
+            for block in &blocks {
+                inspect::inspect(block)?;
+            }
+            Ok(())
+        }
+        Some(Command::Convert {
+            dirs,
+            to,
+            remove_source,
+        }) => {
+            for dir in &dirs {
+                block_format::convert(dir, to, remove_source)?;
+            }
+            Ok(())
+        }
+        None => match cli.eval {
+            Some(args) => eval(args),
+            None => {
+                Cli::command().print_help()?;
+                Ok(())
+            }
+        },
+    }
+}
+
+fn migrate(files: &[PathBuf]) -> Result<()> {
+    let header = csv_header(&PerformanceReport::default())?;
+    for file in files {
+        let dropped = migrate_csv(file, &header)?;
+        if dropped.is_empty() {
+            println!("Migrated {}", file.display());
+        } else {
+            println!(
+                "Migrated {}, dropped columns: {}",
+                file.display(),
+                dropped.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
 
+fn eval(args: EvalArgs) -> Result<()> {
     if args.prover != ProverId::SP1 {
         // Initialize tracing
         tracing_subscriber::FmtSubscriber::builder()
@@ -108,43 +401,163 @@ fn main() -> Result<()> {
             .init();
     }
 
+    // Write the results of each input as it is evaluated. A failed block of a batch is recorded
+    // and the batch goes on, the failure of a single input ends the evaluation. A timed out block
+    // ends the batch too, since its stage is still running.
+    let mut summary = BatchSummary::default();
+    let mut failure = None;
+    let mut reporter = |input_args: &EvalArgs,
+                        reports: Result<Vec<PerformanceReport>, EvalError>| {
+        match reports {
+            Ok(reports) => {
+                write_reports(input_args, &reports)?;
+                summary.add(&reports);
+            }
+            Err(err) => {
+                record_failure(input_args, &err)?;
+                if args.is_batch() {
+                    let block = input_args.block_name.as_deref().unwrap_or_default();
+                    tracing::warn!("Block {block} failed: {err}");
+                    summary.add_failure();
+                }
+                if !args.is_batch() || matches!(err, EvalError::Timeout { .. }) {
+                    failure = Some(err);
+                }
+            }
+        }
+        Ok(())
+    };
+
     // Select the correct implementation based on the prover.
-    let report = match args.prover {
-        ProverId::Risc0 => Risc0Evaluator::eval(&args),
-        ProverId::SP1 => SP1Evaluator::eval(&args),
-        ProverId::Jolt => JoltEvaluator::eval(&args),
-        ProverId::Nexus => NexusEvaluator::eval(&args),
+    let result = match args.prover {
+        ProverId::Risc0 => Risc0Evaluator::eval(&args, &mut reporter),
+        ProverId::SP1 => SP1Evaluator::eval(&args, &mut reporter),
+        ProverId::Jolt => JoltEvaluator::eval(&args, &mut reporter),
+        ProverId::Nexus => NexusEvaluator::eval(&args, &mut reporter),
+        ProverId::Native => NativeEvaluator::eval(&args, &mut reporter),
     };
+    if let Err(err) = result {
+        // Record failures before any input was evaluated, such as a guest that was not built.
+        if let Some(eval_err) = err.downcast_ref::<EvalError>() {
+            record_failure(&args, eval_err)?;
+        }
+        return Err(err);
+    }
+
+    if args.is_batch() {
+        if summary.failed_blocks() > 0 {
+            println!("{} blocks failed", summary.failed_blocks());
+        }
+        write_results(
+            &format!("{}_summary", args.filename),
+            args.format,
+            &RunMetadata::new(&args),
+            &[summary.report(&args)],
+        )?;
+    }
+
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
 
-    // Create the results directory if it doesn't exist.
-    let results_dir = PathBuf::from("results");
-    create_dir_all(&results_dir)?;
+/// Writes the failure of an evaluation in place of its results.
+pub fn record_failure(args: &EvalArgs, err: &EvalError) -> Result<()> {
+    let report = PerformanceReport::failure(args, err.to_string(), Some(err.stage()));
+    write_results(
+        &args.filename,
+        args.format,
+        &RunMetadata::new(args),
+        &[report],
+    )
+}
 
-    // Create the file
-    let filename = format!("{}_{}.csv", args.filename, env!("VERGEN_GIT_SHA"));
-    let path = results_dir.join(filename);
+/// Writes the reports of an input and the breakdowns of its execution and stages.
+fn write_reports(args: &EvalArgs, reports: &[PerformanceReport]) -> Result<()> {
+    let metadata = RunMetadata::new(args);
+    write_results(&args.filename, args.format, &metadata, reports)?;
 
-    // Check if file exists and get its size
-    let file_exists = path.exists();
+    for report in reports {
+        write_breakdowns(args, &metadata, report)?;
+    }
 
-    // Create a CSV writer with appropriate configuration
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .append(file_exists) // Only append if file exists
-        .open(path.clone())?;
+    Ok(())
+}
 
-    let mut writer = WriterBuilder::new()
-        .has_headers(!file_exists) // Write headers only for new files
-        .from_writer(file);
+/// Writes the breakdowns of the execution and stages of a report, with the statistic of the report
+/// when there are several trials.
+fn write_breakdowns(
+    args: &EvalArgs,
+    metadata: &RunMetadata,
+    report: &PerformanceReport,
+) -> Result<()> {
+    let regions: Vec<_> = report
+        .regions
+        .iter()
+        .map(|(region, cycles)| CycleRegionReport {
+            program: args.program.name().to_string(),
+            block: args.block_name.clone(),
+            prover: args.prover.to_string(),
+            statistic: report.statistic,
+            region: region.clone(),
+            cycles: *cycles,
+        })
+        .collect();
+    if !regions.is_empty() {
+        write_results(
+            &format!("{}_cycles", args.filename),
+            args.format,
+            metadata,
+            &regions,
+        )?;
+    }
 
-    // Serialize the report - headers will be written automatically for new files
-    writer.serialize(&report)?;
-    writer.flush()?;
+    let syscalls: Vec<_> = report
+        .syscalls
+        .iter()
+        .map(|(syscall, count)| SyscallReport {
+            program: args.program.name().to_string(),
+            block: args.block_name.clone(),
+            prover: args.prover.to_string(),
+            statistic: report.statistic,
+            syscall: syscall.clone(),
+            count: *count,
+        })
+        .collect();
+    if !syscalls.is_empty() {
+        write_results(
+            &format!("{}_syscalls", args.filename),
+            args.format,
+            metadata,
+            &syscalls,
+        )?;
+    }
 
-    let latest_filename = format!("{}_latest.csv", args.filename);
-    let latest_path = results_dir.join(latest_filename);
-    std::fs::copy(&path, &latest_path)?;
+    let stage_profiles: Vec<_> = report
+        .stage_profiles
+        .iter()
+        .map(|(stage, profile)| StageProfileReport {
+            program: args.program.name().to_string(),
+            block: args.block_name.clone(),
+            prover: args.prover.to_string(),
+            statistic: report.statistic,
+            stage,
+            duration: profile.duration.as_secs_f64(),
+            peak_rss: profile.peak_rss,
+            cpu_utilisation: profile.cpu_utilisation(),
+            user_time: profile.user_time,
+            system_time: profile.system_time,
+        })
+        .collect();
+    if !stage_profiles.is_empty() {
+        write_results(
+            &format!("{}_stages", args.filename),
+            args.format,
+            metadata,
+            &stage_profiles,
+        )?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,120 @@
+#[cfg(feature = "native")]
+use anyhow::{anyhow, bail, Result};
+#[cfg(feature = "native")]
+use raiko_lib::{
+    builder::calculate_block_header, input::GuestInput, proof_type::ProofType,
+    protocol_instance::ProtocolInstance,
+};
+#[cfg(feature = "native")]
+use rsp_client_executor::{io::ClientExecutorInput, ClientExecutor, EthereumVariant};
+
+#[cfg(feature = "native")]
+use crate::{
+    evaluator::{self, Execution, StageProof, ZkvmEvaluator},
+    types::Stage,
+};
+
+use crate::{error::EvalError, evaluator::Reporter, EvalArgs};
+
+/// Runs the guest logic directly on the host with the same inputs as the zkVMs and reports its wall
+/// time. The overhead of a zkVM is not computed here, it is the ratio of the durations of its rows
+/// and the native rows of the same blocks. Only the execute stage is supported.
+#[cfg(feature = "native")]
+pub struct NativeEvaluator {
+    program: NativeProgram,
+    input: Vec<u8>,
+}
+
+/// The programs whose guest logic can run on the host.
+#[cfg(feature = "native")]
+#[derive(Clone, Copy)]
+enum NativeProgram {
+    Raiko,
+    Reth,
+}
+
+#[cfg(not(feature = "native"))]
+pub struct NativeEvaluator;
+
+#[cfg(feature = "native")]
+impl ZkvmEvaluator for NativeEvaluator {
+    type CoreProof = ();
+    type CompressedProof = ();
+    type WrapProof = ();
+
+    fn setup(args: &EvalArgs) -> Result<Self> {
+        let program = match args.program.name() {
+            "raiko" => NativeProgram::Raiko,
+            "reth" => NativeProgram::Reth,
+            _ => bail!("Native execution is only supported for the raiko and reth programs"),
+        };
+
+        Ok(NativeEvaluator {
+            program,
+            input: Vec::new(),
+        })
+    }
+
+    fn write_input(&mut self, input: &[Vec<u8>]) -> Result<()> {
+        self.input = input.concat();
+        Ok(())
+    }
+
+    fn execute(&mut self) -> Result<Execution> {
+        // Decoding the input is part of the measurement, as it is for the guests.
+        let public_values = match self.program {
+            NativeProgram::Raiko => {
+                let input = bincode::deserialize::<GuestInput>(&self.input)?;
+                let header = calculate_block_header(&input);
+                let pi = ProtocolInstance::new(&input, &header, ProofType::Native)?.instance_hash();
+                pi.0.to_vec()
+            }
+            NativeProgram::Reth => {
+                let input = bincode::deserialize::<ClientExecutorInput>(&self.input)?;
+                // The client executor reports `eyre` errors.
+                let header = ClientExecutor
+                    .execute::<EthereumVariant>(input)
+                    .map_err(|err| anyhow!("{err:#}"))?;
+                header.hash_slow().to_vec()
+            }
+        };
+
+        // There are no cycles outside of a zkVM, only the wall time is reported.
+        Ok(Execution {
+            cycles: None,
+            public_values,
+            regions: Vec::new(),
+            syscalls: Vec::new(),
+        })
+    }
+
+    fn prove_core(&mut self) -> Result<StageProof<Self::CoreProof>> {
+        Err(EvalError::Setup("native execution has no proving stages".into()).into())
+    }
+
+    fn verify_core(&mut self, _proof: &Self::CoreProof) -> Result<()> {
+        Err(EvalError::Setup("native execution has no proving stages".into()).into())
+    }
+}
+
+impl NativeEvaluator {
+    #[cfg(feature = "native")]
+    pub fn eval(args: &EvalArgs, reporter: &mut Reporter) -> eyre::Result<()> {
+        if args.stages.iter().any(|stage| *stage != Stage::Execute) {
+            tracing::warn!("Native execution only supports the execute stage, skipping the others");
+        }
+        let args = EvalArgs {
+            stages: vec![Stage::Execute],
+            ..args.clone()
+        };
+        evaluator::run::<Self>(&args, reporter)
+    }
+
+    #[cfg(not(feature = "native"))]
+    pub fn eval(_args: &EvalArgs, _reporter: &mut Reporter) -> eyre::Result<()> {
+        Err(EvalError::Setup(
+            "Native feature is not enabled. Please compile with --features native".into(),
+        )
+        .into())
+    }
+}
@@ -1,78 +1,125 @@
 #[cfg(feature = "nexus")]
 use std::fs;
 
+#[cfg(feature = "nexus")]
+use anyhow::Result;
+
 #[cfg(feature = "nexus")]
 use nexus_sdk::{
-    nova::seq::{Generate, Nova, PP},
+    nova::seq::{Generate, Nova, Proof, PP},
     Prover, Verifiable,
 };
 
 #[cfg(feature = "nexus")]
-use crate::utils::{get_elf, time_operation};
+use crate::{
+    evaluator::{self, Execution, StageProof, ZkvmEvaluator},
+    utils::get_elf,
+};
+
+use crate::{error::EvalError, evaluator::Reporter, EvalArgs};
 
-use crate::{EvalArgs, PerformanceReport};
+#[cfg(feature = "nexus")]
+pub struct NexusEvaluator {
+    elf: Vec<u8>,
+    pp: Option<PP>,
+    input: Option<u32>,
+    prover: Option<Nova>,
+}
 
+#[cfg(not(feature = "nexus"))]
 pub struct NexusEvaluator;
 
-impl NexusEvaluator {
-    #[cfg(feature = "nexus")]
-    pub fn eval(args: &EvalArgs) -> PerformanceReport {
-        use crate::ProgramId;
+#[cfg(feature = "nexus")]
+impl ZkvmEvaluator for NexusEvaluator {
+    type CoreProof = Proof;
+    type CompressedProof = ();
+    type WrapProof = ();
+
+    const SHARDED: bool = false;
+    const LABEL_INPUT: bool = false;
 
-        let elf_path = get_elf(args);
-        let elf = fs::read(&elf_path).unwrap();
+    fn setup(args: &EvalArgs) -> Result<Self> {
+        let elf_path = get_elf(args)?;
+        let elf = fs::read(&elf_path)?;
+
+        Ok(NexusEvaluator {
+            elf,
+            pp: None,
+            input: None,
+            prover: None,
+        })
+    }
 
-        let pp: PP = PP::generate().expect("failed to generate parameters");
+    fn setup_prover(&mut self) -> Result<()> {
+        self.pp = Some(PP::generate()?);
+        Ok(())
+    }
+
+    fn write_input(&mut self, input: &[Vec<u8>]) -> Result<()> {
+        // The only typed input the guests take is the fibonacci `u32`.
+        self.input = input
+            .first()
+            .map(|buffer| bincode::deserialize(buffer))
+            .transpose()?;
+        Ok(())
+    }
 
+    fn execute(&mut self) -> Result<Execution> {
         // Generate the prover. Arithmetization should be made at this step
-        let (prover, execution_duration) =
-            time_operation(|| Nova::new(&elf).expect("failed to load program"));
+        self.prover = Some(Nova::new(&self.elf)?);
+
+        // Nexus does not report cycle counts and the guests do not commit any public values.
+        Ok(Execution {
+            cycles: None,
+            public_values: Vec::new(),
+            regions: Vec::new(),
+            syscalls: Vec::new(),
+        })
+    }
+
+    fn prove_core(&mut self) -> Result<StageProof<Self::CoreProof>> {
+        let prover = self
+            .prover
+            .take()
+            .ok_or_else(|| EvalError::Setup("program must be loaded before proving".into()))?;
+        let pp = self.pp()?;
 
         // Generate the proof.
-        let (proof, core_prove_duration) = match args.program {
-            ProgramId::Fibonacci => time_operation(|| {
-                prover
-                    .prove_with_input::<u32>(
-                        &pp,
-                        &args.fibonacci_input.expect("missing fibonacci input"),
-                    )
-                    .expect("failed to prove program")
-            }),
-            _ => time_operation(|| prover.prove(&pp).expect("failed to prove program")),
+        let proof = match self.input {
+            Some(input) => prover.prove_with_input::<u32>(pp, &input)?,
+            None => prover.prove(pp)?,
         };
 
-        // Verify the proof
-        let (_, core_verify_duration) =
-            time_operation(|| proof.verify(&pp).expect("failed to verify program"));
-
-        let prove_duration = core_prove_duration;
-
-        // Create the performance report
-        PerformanceReport {
-            program: args.program.to_string(),
-            prover: args.prover.to_string(),
-            hashfn: "".to_string(),
-            shards: 0,
-            shard_size: 0,
-            cycles: 0,
-            speed: 0.0,
-            execution_duration: execution_duration.as_secs_f64(),
-            prove_duration: prove_duration.as_secs_f64(),
-            core_prove_duration: core_prove_duration.as_secs_f64(),
-            core_verify_duration: core_verify_duration.as_secs_f64(),
-            core_proof_size: 0,
-            compress_proof_size: 0,
-            compress_verify_duration: 0.0,
-            compress_prove_duration: 0.0,
-            wrap_prove_duration: 0.0,
-            groth16_prove_duration: 0.0,
-            shrink_prove_duration: 0.0,
-            wrap_prove_duration: 0.0,
-        }
+        // The size of Nexus proofs is not measured.
+        Ok(StageProof { proof, size: None })
+    }
+
+    fn verify_core(&mut self, proof: &Self::CoreProof) -> Result<()> {
+        proof.verify(self.pp()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "nexus")]
+impl NexusEvaluator {
+    fn pp(&self) -> Result<&PP, EvalError> {
+        self.pp
+            .as_ref()
+            .ok_or_else(|| EvalError::Setup("prover must be set up before proving".into()))
+    }
+}
+
+impl NexusEvaluator {
+    #[cfg(feature = "nexus")]
+    pub fn eval(args: &EvalArgs, reporter: &mut Reporter) -> eyre::Result<()> {
+        evaluator::run::<Self>(args, reporter)
     }
 
     #[cfg(not(feature = "nexus"))]
-    pub fn eval(_args: &EvalArgs) -> PerformanceReport {
-        panic!("Nexus feature is not enabled. Please compile with --features nexus");
+    pub fn eval(_args: &EvalArgs, _reporter: &mut Reporter) -> eyre::Result<()> {
+        Err(EvalError::Setup(
+            "Nexus feature is not enabled. Please compile with --features nexus".into(),
+        )
+        .into())
     }
 }
@@ -0,0 +1,403 @@
+use std::{
+    fs::{self, create_dir_all, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use csv::{Reader, ReaderBuilder, StringRecord, WriterBuilder};
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    environment::Environment,
+    types::{OutputFormat, Stage},
+    EvalArgs,
+};
+
+/// The version of the JSON record schema. Bump it when a field is renamed, removed or changes
+/// meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Metadata describing the run that produced a set of results.
+#[derive(Debug, Serialize, Clone)]
+pub struct RunMetadata {
+    /// The program that is being evaluated.
+    pub program: String,
+    /// The block the program was run on, if any.
+    pub block: Option<String>,
+    /// The prover that is being evaluated.
+    pub prover: String,
+    /// The shard size that is being evaluated.
+    pub shard_size: u64,
+    /// The stages that were selected.
+    pub stages: Vec<Stage>,
+    /// The number of measured trials.
+    pub trials: u32,
+    /// The number of unmeasured warm-up trials.
+    pub warmup: u32,
+    /// The cycle limit of the execution, if any.
+    pub max_cycles: Option<u64>,
+    /// The wall-clock timeout of each stage in seconds, if any.
+    pub stage_timeout: Option<u64>,
+    /// The machine, toolchain and build of the run.
+    #[serde(flatten)]
+    pub environment: Environment,
+}
+
+impl RunMetadata {
+    pub fn new(args: &EvalArgs) -> Self {
+        RunMetadata {
+            program: args.program.name().to_string(),
+            block: args.block_name.clone(),
+            prover: args.prover.to_string(),
+            shard_size: args.shard_size,
+            stages: args.stages.clone(),
+            trials: args.trials,
+            warmup: args.warmup,
+            max_cycles: args.max_cycles,
+            stage_timeout: args.stage_timeout,
+            environment: Environment::capture(),
+        }
+    }
+}
+
+/// A row of results as it is written to JSON.
+#[derive(Serialize)]
+struct Record<'a, T> {
+    schema_version: u32,
+    metadata: &'a RunMetadata,
+    #[serde(flatten)]
+    row: &'a T,
+}
+
+/// Appends the rows to `results/<filename>_<commit>.<ext>` and copies it to
+/// `results/<filename>_latest.<ext>`.
+pub fn write_results<T: Serialize>(
+    filename: &str,
+    format: OutputFormat,
+    metadata: &RunMetadata,
+    rows: &[T],
+) -> Result<()> {
+    // Create the results directory if it doesn't exist.
+    let results_dir = PathBuf::from("results");
+    create_dir_all(&results_dir)?;
+
+    let extension = format.extension();
+    let path = results_dir.join(format!(
+        "{}_{}.{}",
+        filename, metadata.environment.git_sha, extension
+    ));
+
+    let records = || {
+        rows.iter().map(|row| Record {
+            schema_version: SCHEMA_VERSION,
+            metadata,
+            row,
+        })
+    };
+    match format {
+        OutputFormat::Csv => write_csv(&path, rows, &metadata.environment)?,
+        OutputFormat::Json => write_json(&path, records())?,
+        OutputFormat::Ndjson => write_ndjson(&path, records())?,
+    }
+
+    let latest_path = results_dir.join(format!("{}_latest.{}", filename, extension));
+    fs::copy(&path, &latest_path)?;
+
+    Ok(())
+}
+
+/// Appends the rows to a CSV, with the environment columns after the row columns.
+fn write_csv<T: Serialize>(path: &Path, rows: &[T], environment: &Environment) -> Result<()> {
+    let (mut header, mut records) = to_records(rows)?;
+    let (environment_header, environment_records) = to_records(std::slice::from_ref(environment))?;
+    header.extend(environment_header.iter());
+    for record in &mut records {
+        record.extend(environment_records[0].iter());
+    }
+
+    if !path.exists() {
+        return rewrite_csv(path, &header, records);
+    }
+
+    let (existing_header, existing_records) = read_csv(path)?;
+    if existing_header == header {
+        // Append without headers, the file already has the same ones.
+        let file = OpenOptions::new().append(true).open(path)?;
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+        for record in &records {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    // The columns changed since the file was written, so rewrite it with the columns of both.
+    let mut merged = existing_header.clone();
+    for column in header.iter() {
+        if !existing_header.iter().any(|existing| existing == column) {
+            merged.push_field(column);
+        }
+    }
+    tracing::warn!(
+        "The columns of {} do not match the current report, rewriting it with merged columns",
+        path.display()
+    );
+
+    let records = existing_records
+        .iter()
+        .map(|record| project(&existing_header, record, &merged))
+        .chain(
+            records
+                .iter()
+                .map(|record| project(&header, record, &merged)),
+        )
+        .collect();
+    rewrite_csv(path, &merged, records)
+}
+
+/// Rewrites a results CSV with the given columns, dropping the columns it does not have and leaving
+/// the missing ones empty. Returns the columns that were dropped.
+pub fn migrate_csv(path: &Path, header: &StringRecord) -> Result<Vec<String>> {
+    let (existing_header, existing_records) = read_csv(path)?;
+    let dropped = existing_header
+        .iter()
+        .filter(|column| !header.iter().any(|current| current == *column))
+        .map(str::to_string)
+        .collect();
+
+    let records = existing_records
+        .iter()
+        .map(|record| project(&existing_header, record, header))
+        .collect();
+    rewrite_csv(path, header, records)?;
+
+    Ok(dropped)
+}
+
+/// The CSV header a row type is written with, including the environment columns.
+pub fn csv_header<T: Serialize>(row: &T) -> Result<StringRecord> {
+    let mut header = to_records(std::slice::from_ref(row))?.0;
+    header.extend(to_records(&[Environment::default()])?.0.iter());
+    Ok(header)
+}
+
+/// Serializes the rows to CSV records along with their header.
+fn to_records<T: Serialize>(rows: &[T]) -> Result<(StringRecord, Vec<StringRecord>)> {
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let buffer = writer.into_inner().map_err(|err| err.into_error())?;
+    read_records(ReaderBuilder::new().from_reader(buffer.as_slice()))
+}
+
+fn read_csv(path: &Path) -> Result<(StringRecord, Vec<StringRecord>)> {
+    // Files written by other scripts may have rows of different lengths.
+    read_records(ReaderBuilder::new().flexible(true).from_path(path)?)
+}
+
+fn read_records<R: Read>(mut reader: Reader<R>) -> Result<(StringRecord, Vec<StringRecord>)> {
+    let header = reader.headers()?.clone();
+    let records = reader.records().collect::<Result<_, _>>()?;
+    Ok((header, records))
+}
+
+/// Reorders the fields of a record to the target columns, leaving missing ones empty.
+fn project(header: &StringRecord, record: &StringRecord, target: &StringRecord) -> StringRecord {
+    target
+        .iter()
+        .map(|column| {
+            header
+                .iter()
+                .position(|existing| existing == column)
+                .and_then(|index| record.get(index))
+                .unwrap_or("")
+        })
+        .collect()
+}
+
+fn rewrite_csv(path: &Path, header: &StringRecord, records: Vec<StringRecord>) -> Result<()> {
+    // Write to a temporary file first so the results are not lost if writing fails.
+    let tmp_path = path.with_extension("csv.tmp");
+    let mut writer = WriterBuilder::new().from_path(&tmp_path)?;
+    writer.write_record(header)?;
+    for record in &records {
+        writer.write_record(record)?;
+    }
+    writer.flush()?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+fn write_json<R: Serialize>(path: &Path, records: impl Iterator<Item = R>) -> Result<()> {
+    // A JSON array can't be appended to, so the existing records are read back first.
+    let mut values: Vec<serde_json::Value> = if path.exists() {
+        serde_json::from_slice(&fs::read(path)?)?
+    } else {
+        Vec::new()
+    };
+    for record in records {
+        values.push(serde_json::to_value(record)?);
+    }
+    fs::write(path, serde_json::to_vec_pretty(&values)?)?;
+
+    Ok(())
+}
+
+fn write_ndjson<R: Serialize>(path: &Path, records: impl Iterator<Item = R>) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for record in records {
+        serde_json::to_writer(&mut file, &record)?;
+        writeln!(file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::PerformanceReport;
+
+    #[derive(Serialize)]
+    struct Row {
+        program: &'static str,
+        cycles: u64,
+        prover: &'static str,
+    }
+
+    const ROW: Row = Row {
+        program: "fibonacci",
+        cycles: 100,
+        prover: "sp1",
+    };
+
+    fn read(path: &Path) -> (StringRecord, Vec<StringRecord>) {
+        read_csv(path).unwrap()
+    }
+
+    #[test]
+    fn appends_rows_with_the_same_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        write_csv(&path, &[ROW], &Environment::default()).unwrap();
+        write_csv(&path, &[ROW], &Environment::default()).unwrap();
+
+        let (header, records) = read(&path);
+        assert_eq!(header, csv_header(&ROW).unwrap());
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn merges_an_older_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        fs::write(&path, "program,cycles,retired\nloop,50,x\n").unwrap();
+        write_csv(&path, &[ROW], &Environment::default()).unwrap();
+
+        let (header, records) = read(&path);
+        let columns: Vec<_> = header.iter().take(4).collect();
+        assert_eq!(columns, ["program", "cycles", "retired", "prover"]);
+        assert_eq!(header.len(), csv_header(&ROW).unwrap().len() + 1);
+
+        // The old row keeps its values and leaves the new columns empty.
+        let old: Vec<_> = records[0].iter().take(4).collect();
+        assert_eq!(old, ["loop", "50", "x", ""]);
+        let new: Vec<_> = records[1].iter().take(4).collect();
+        assert_eq!(new, ["fibonacci", "100", "", "sp1"]);
+    }
+
+    #[test]
+    fn migrates_a_pre_schema_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        fs::write(
+            &path,
+            "program,prover,cycles,speed,lita_only\nloop,lita,1000,2.5,y\n",
+        )
+        .unwrap();
+
+        let header = csv_header(&PerformanceReport::default()).unwrap();
+        let dropped = migrate_csv(&path, &header).unwrap();
+        assert_eq!(dropped, ["lita_only"]);
+
+        let (migrated_header, records) = read(&path);
+        assert_eq!(migrated_header, header);
+        let value = |column: &str| {
+            let index = header
+                .iter()
+                .position(|existing| existing == column)
+                .unwrap();
+            records[0][index].to_string()
+        };
+        assert_eq!(value("program"), "loop");
+        assert_eq!(value("prover"), "lita");
+        assert_eq!(value("cycles"), "1000");
+        assert_eq!(value("speed"), "2.5");
+        assert_eq!(value("error"), "");
+    }
+
+    #[test]
+    fn appends_json_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        write_json(&path, [json!({"cycles": 1})].into_iter()).unwrap();
+        write_json(
+            &path,
+            [json!({"cycles": 2}), json!({"cycles": 3})].into_iter(),
+        )
+        .unwrap();
+
+        let values: Vec<Value> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            values,
+            [
+                json!({"cycles": 1}),
+                json!({"cycles": 2}),
+                json!({"cycles": 3})
+            ]
+        );
+    }
+
+    #[test]
+    fn appends_ndjson_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.ndjson");
+        let metadata = RunMetadata {
+            program: "fibonacci".into(),
+            block: None,
+            prover: "sp1".into(),
+            shard_size: 22,
+            stages: vec![Stage::Execute],
+            trials: 1,
+            warmup: 0,
+            max_cycles: None,
+            stage_timeout: None,
+            environment: Environment::default(),
+        };
+        let record = Record {
+            schema_version: SCHEMA_VERSION,
+            metadata: &metadata,
+            row: &ROW,
+        };
+        write_ndjson(&path, [&record].into_iter()).unwrap();
+        write_ndjson(&path, [&record].into_iter()).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let values: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["schema_version"], SCHEMA_VERSION);
+        assert_eq!(values[0]["metadata"]["program"], "fibonacci");
+        assert_eq!(values[0]["metadata"]["shard_size"], 22);
+        // The row is flattened next to the metadata.
+        assert_eq!(values[1]["cycles"], 100);
+        assert_eq!(values[1]["prover"], "sp1");
+    }
+}
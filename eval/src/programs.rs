@@ -0,0 +1,335 @@
+use std::{env, fmt, path::PathBuf};
+
+use block_manifest::BlockFormat;
+use raiko_lib::{
+    builder::calculate_block_header, input::GuestInput, proof_type::ProofType,
+    protocol_instance::ProtocolInstance,
+};
+use rsp_client_executor::{io::ClientExecutorInput, ClientExecutor, EthereumVariant};
+use serde::Serialize;
+
+use crate::{
+    error::EvalError,
+    types::ProverId,
+    utils::{get_raiko_input, get_reth_input, raiko_blocks_dir, reth_blocks_dir},
+    EvalArgs,
+};
+
+/// A directory of blocks and the extensions of the block files in it.
+pub type BlocksDir = (PathBuf, &'static [&'static str]);
+
+/// A guest program that can be benchmarked.
+///
+/// New programs are added by implementing this trait and registering them in [PROGRAMS].
+pub trait BenchmarkProgram: Sync {
+    /// The name used to select the program on the command line.
+    fn name(&self) -> &'static str;
+
+    /// The name of the program as it appears in the report, including its input.
+    fn label(&self, _args: &EvalArgs) -> String {
+        self.name().to_string()
+    }
+
+    /// The directory under `benchmarks/` that holds the guest for the given prover.
+    fn guest_dir(&self, _prover: &ProverId) -> String {
+        self.name().to_string()
+    }
+
+    /// The path of the guest ELF for the given prover.
+    fn elf_path(&self, prover: &ProverId) -> Result<PathBuf, EvalError> {
+        let guest_dir = self.guest_dir(prover);
+        let target_name = prover
+            .target_triple()
+            .ok_or_else(|| EvalError::Setup(format!("{prover} does not load a guest ELF")))?;
+        let current_dir = env::current_dir().map_err(|e| {
+            EvalError::Setup(format!("Failed to get current working directory: {e}"))
+        })?;
+        Ok(current_dir.join(format!(
+            "benchmarks/{}/target/{}/release/{}",
+            guest_dir, target_name, guest_dir
+        )))
+    }
+
+    /// The input of the program for the given prover. Called once per input of an evaluation, so
+    /// programs that take a block read and decode it once.
+    fn input(&self, _args: &EvalArgs, _prover: &ProverId) -> Result<ProgramInput, EvalError> {
+        Ok(ProgramInput::default())
+    }
+
+    /// The directory the program reads its blocks from and the extensions of the block files, for
+    /// programs that take a block as input.
+    fn blocks_dir(&self, _args: &EvalArgs) -> Result<Option<BlocksDir>, EvalError> {
+        Ok(None)
+    }
+}
+
+/// The input of a program, and what the host knows about it.
+#[derive(Default)]
+pub struct ProgramInput {
+    /// The guest input encoded for the prover, as buffers the guest reads in order.
+    pub buffers: Vec<Vec<u8>>,
+    /// The public output the guest is expected to commit, if it can be computed on the host.
+    pub expected_output: Option<Vec<u8>>,
+    /// The facts about the block the program is run on, for programs that take a block as input.
+    pub block_info: Option<BlockInfo>,
+}
+
+/// The facts about a block, used to normalise the measurements of the programs that execute it and
+/// printed by `eval inspect`.
+#[derive(Debug, Clone)]
+pub struct BlockInfo {
+    /// The number of the block.
+    pub number: u64,
+    /// The chain the block belongs to.
+    pub chain: String,
+    /// The gas used by the block.
+    pub gas_used: u64,
+    /// The number of transactions in the block.
+    pub transactions: usize,
+    /// The number of accounts in the state witness.
+    pub accounts: usize,
+    /// The number of storage slots in the state witness.
+    pub storage_slots: usize,
+    /// How the blob of a Taiko block is proven, if the block is a Taiko block.
+    pub blob_proof_type: Option<String>,
+}
+
+impl BlockInfo {
+    pub fn from_guest_input(input: &GuestInput) -> Self {
+        BlockInfo {
+            number: input.block.header.number,
+            chain: input.chain_spec.name.clone(),
+            gas_used: input.block.header.gas_used,
+            transactions: input.block.body.len(),
+            accounts: input.parent_storage.len(),
+            storage_slots: input
+                .parent_storage
+                .values()
+                .map(|(_, slots)| slots.len())
+                .sum(),
+            blob_proof_type: Some(format!("{:?}", input.taiko.blob_proof_type)),
+        }
+    }
+
+    pub fn from_client_input(input: &ClientExecutorInput) -> Self {
+        let block = &input.current_block;
+        BlockInfo {
+            number: block.header.number,
+            // The reth guests execute Ethereum mainnet blocks.
+            chain: "ethereum".to_string(),
+            gas_used: block.header.gas_used,
+            transactions: block.body.len(),
+            accounts: input.state_requests.len(),
+            storage_slots: input.state_requests.values().map(Vec::len).sum(),
+            blob_proof_type: None,
+        }
+    }
+}
+
+impl fmt::Display for dyn BenchmarkProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// All programs that can be benchmarked.
+pub static PROGRAMS: &[&dyn BenchmarkProgram] = &[&Loop, &Fibonacci, &Tendermint, &Reth, &Raiko];
+
+/// Looks up a program by name. Used as the clap value parser for `--program`.
+pub fn parse_program(name: &str) -> Result<&'static dyn BenchmarkProgram, String> {
+    PROGRAMS
+        .iter()
+        .copied()
+        .find(|program| program.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = PROGRAMS.iter().map(|program| program.name()).collect();
+            format!(
+                "unknown program '{name}', expected one of: {}",
+                names.join(", ")
+            )
+        })
+}
+
+/// Serializes a value with the serializer the guest uses on the given prover to read its input and
+/// commit its public output.
+///
+/// Jolt, Nexus and native execution take typed inputs, so their evaluators decode the bincode
+/// buffer again.
+pub fn encode<T: Serialize>(value: &T, prover: &ProverId) -> Result<Vec<u8>, EvalError> {
+    match prover {
+        ProverId::Risc0 => encode_risc0(value),
+        ProverId::SP1 | ProverId::Jolt | ProverId::Nexus | ProverId::Native => {
+            bincode::serialize(value)
+                .map_err(|e| EvalError::Setup(format!("Failed to serialize input: {e}")))
+        }
+    }
+}
+
+#[cfg(feature = "risc0")]
+fn encode_risc0<T: Serialize>(value: &T) -> Result<Vec<u8>, EvalError> {
+    let words = risc0_zkvm::serde::to_vec(value)
+        .map_err(|e| EvalError::Setup(format!("Could not serialize proving input: {e}")))?;
+    Ok(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+#[cfg(not(feature = "risc0"))]
+fn encode_risc0<T: Serialize>(_value: &T) -> Result<Vec<u8>, EvalError> {
+    Err(EvalError::Setup(
+        "RISC0 feature is not enabled. Please compile with --features risc0".into(),
+    ))
+}
+
+/// The label of a program run on a block, or its name if the block is missing.
+fn block_label(name: &str, args: &EvalArgs) -> String {
+    match &args.block_name {
+        Some(block_name) => format!("{}_{}", name, block_name),
+        None => name.to_string(),
+    }
+}
+
+pub struct Loop;
+
+impl BenchmarkProgram for Loop {
+    fn name(&self) -> &'static str {
+        "loop"
+    }
+}
+
+pub struct Fibonacci;
+
+impl BenchmarkProgram for Fibonacci {
+    fn name(&self) -> &'static str {
+        "fibonacci"
+    }
+
+    fn label(&self, args: &EvalArgs) -> String {
+        match args.fibonacci_input {
+            Some(input) => format!("{}_{}", self.name(), input),
+            None => self.name().to_string(),
+        }
+    }
+
+    fn input(&self, args: &EvalArgs, prover: &ProverId) -> Result<ProgramInput, EvalError> {
+        let input = args
+            .fibonacci_input
+            .ok_or_else(|| EvalError::MissingInput("fibonacci_input not provided".into()))?;
+        Ok(ProgramInput {
+            buffers: vec![encode(&input, prover)?],
+            ..Default::default()
+        })
+    }
+}
+
+pub struct Tendermint;
+
+impl BenchmarkProgram for Tendermint {
+    fn name(&self) -> &'static str {
+        "tendermint"
+    }
+
+    fn guest_dir(&self, prover: &ProverId) -> String {
+        format!("{}-{}", self.name(), prover)
+    }
+}
+
+pub struct Reth;
+
+impl BenchmarkProgram for Reth {
+    fn name(&self) -> &'static str {
+        "reth"
+    }
+
+    fn label(&self, args: &EvalArgs) -> String {
+        block_label(self.name(), args)
+    }
+
+    fn guest_dir(&self, prover: &ProverId) -> String {
+        format!("{}-{}", self.name(), prover)
+    }
+
+    fn input(&self, args: &EvalArgs, prover: &ProverId) -> Result<ProgramInput, EvalError> {
+        // The guest reads the bincode-encoded `ClientExecutorInput` as raw bytes.
+        let bytes = get_reth_input(args)?;
+        let input = bincode::deserialize::<ClientExecutorInput>(&bytes)
+            .map_err(|e| EvalError::MissingInput(format!("Failed to decode reth block: {e}")))?;
+        let block_info = BlockInfo::from_client_input(&input);
+
+        let expected_output = match prover {
+            ProverId::SP1 | ProverId::Risc0 => {
+                // Execute the block natively to compute the block hash the guest commits.
+                let header = ClientExecutor
+                    .execute::<EthereumVariant>(input)
+                    .map_err(|e| {
+                        EvalError::Execution(format!("Failed to execute reth block: {e:#}"))
+                    })?;
+
+                // Both guests commit the raw hash bytes.
+                Some(encode(&header.hash_slow().0, prover)?)
+            }
+            _ => None,
+        };
+
+        Ok(ProgramInput {
+            buffers: vec![bytes],
+            expected_output,
+            block_info: Some(block_info),
+        })
+    }
+
+    fn blocks_dir(&self, args: &EvalArgs) -> Result<Option<BlocksDir>, EvalError> {
+        Ok(Some((reth_blocks_dir(args)?, &["bin"])))
+    }
+}
+
+pub struct Raiko;
+
+impl BenchmarkProgram for Raiko {
+    fn name(&self) -> &'static str {
+        "raiko"
+    }
+
+    fn label(&self, args: &EvalArgs) -> String {
+        block_label(self.name(), args)
+    }
+
+    fn guest_dir(&self, prover: &ProverId) -> String {
+        format!("{}-{}", self.name(), prover)
+    }
+
+    fn input(&self, args: &EvalArgs, prover: &ProverId) -> Result<ProgramInput, EvalError> {
+        let input = get_raiko_input(args)?;
+        Ok(ProgramInput {
+            buffers: vec![encode(&input, prover)?],
+            expected_output: raiko_expected_output(&input, prover)?,
+            block_info: Some(BlockInfo::from_guest_input(&input)),
+        })
+    }
+
+    fn blocks_dir(&self, args: &EvalArgs) -> Result<Option<BlocksDir>, EvalError> {
+        Ok(Some((raiko_blocks_dir(args)?, BlockFormat::EXTENSIONS)))
+    }
+}
+
+/// The public output the `raiko` guest of a prover commits for a block.
+fn raiko_expected_output(
+    input: &GuestInput,
+    prover: &ProverId,
+) -> Result<Option<Vec<u8>>, EvalError> {
+    let proof_type = match prover {
+        ProverId::SP1 => ProofType::Sp1,
+        ProverId::Risc0 => ProofType::Risc0,
+        _ => return Ok(None),
+    };
+
+    // Run the guest logic natively to compute the instance hash the guest commits.
+    let header = calculate_block_header(input);
+    let pi = ProtocolInstance::new(input, &header, proof_type)
+        .map_err(|e| EvalError::MissingInput(format!("Invalid protocol instance: {e}")))?
+        .instance_hash();
+
+    // The SP1 guest commits the raw hash bytes, the RISC0 guest commits the hash itself.
+    match prover {
+        ProverId::SP1 => Ok(Some(encode(&pi.0, prover)?)),
+        _ => Ok(Some(encode(&pi, prover)?)),
+    }
+}
@@ -1,169 +1,263 @@
 #[cfg(feature = "risc0")]
-use std::fs;
+use std::{cell::RefCell, fs, io::Write, rc::Rc};
+
+#[cfg(feature = "risc0")]
+use anyhow::Result;
 
 #[cfg(feature = "risc0")]
 use risc0_zkvm::{
-    compute_image_id, get_prover_server, ExecutorEnv, ExecutorImpl, ProverOpts, VerifierContext,
+    compute_image_id, get_prover_server, sha::Digest, ExecutorEnv, ExecutorImpl, ProverOpts,
+    ProverServer, Receipt, ReceiptClaim, Session, SuccinctReceipt, VerifierContext,
 };
 
 #[cfg(feature = "risc0")]
 use crate::{
-    types::ProgramId,
-    utils::{get_elf, get_raiko_input, get_reth_input, time_operation},
+    evaluator::{self, Execution, StageProof, ZkvmEvaluator},
+    utils::get_elf,
 };
 
-use crate::{EvalArgs, PerformanceReport};
+use crate::{error::EvalError, evaluator::Reporter, EvalArgs};
+
+#[cfg(feature = "risc0")]
+pub struct Risc0Evaluator {
+    elf: Vec<u8>,
+    image_id: Digest,
+    segment_limit_po2: u32,
+    max_cycles: Option<u64>,
+    input: Vec<u8>,
+    executor: Option<ExecutorImpl<'static>>,
+    stdout: GuestOutput,
+    session: Option<Session>,
+    prover: Rc<dyn ProverServer>,
+}
 
+#[cfg(not(feature = "risc0"))]
 pub struct Risc0Evaluator;
 
-impl Risc0Evaluator {
-    #[cfg(feature = "risc0")]
-    pub fn eval(args: &EvalArgs) -> PerformanceReport {
+#[cfg(feature = "risc0")]
+impl ZkvmEvaluator for Risc0Evaluator {
+    type CoreProof = Receipt;
+    type CompressedProof = Receipt;
+    type WrapProof = SuccinctReceipt<ReceiptClaim>;
+
+    const SPEED_INCLUDES_COMPRESS: bool = true;
+
+    fn setup(args: &EvalArgs) -> Result<Self> {
         // if args.hashfn != HashFnId::Poseidon {
         //     panic!("Only Poseidon hash function is supported for Risc0.");
         // }
 
-        let program = match args.program {
-            ProgramId::Reth | ProgramId::Raiko => format!(
-                "{}_{}",
-                args.program.to_string(),
-                args.block_name.as_deref().unwrap().to_string()
-            ),
-            ProgramId::Fibonacci => format!(
-                "{}_{}",
-                args.program.to_string(),
-                args.fibonacci_input.unwrap().to_string()
-            ),
-            _ => args.program.to_string(),
-        };
+        let elf_path = get_elf(args)?;
+        let elf = fs::read(&elf_path)?;
+        let image_id = compute_image_id(elf.as_slice())?;
 
-        let elf_path = get_elf(args);
-        let elf = fs::read(&elf_path).unwrap();
-        let image_id = compute_image_id(elf.as_slice()).unwrap();
-
-        // Setup the prover.
-        // If the program is Reth or fibonacci, read the block and set it as
-        // input. Otherwise, others benchmarks don't have an input.
-        let env = match args.program {
-            ProgramId::Reth => {
-                let input = get_reth_input(args);
-                ExecutorEnv::builder()
-                    .segment_limit_po2(args.shard_size as u32)
-                    .write_slice(&input)
-                    .build()
-                    .unwrap()
-            }
-            ProgramId::Fibonacci => ExecutorEnv::builder()
-                .segment_limit_po2(args.shard_size as u32)
-                .write(&args.fibonacci_input.expect("missing fibonacci input"))
-                .expect("Failed to write input to executor")
-                .build()
-                .unwrap(),
-            ProgramId::Raiko => {
-                let input = get_raiko_input(args);
-                let encoded_input = risc0_zkvm::serde::to_vec(&input).expect("Could not serialize proving input!");
-                ExecutorEnv::builder()
-                    .session_limit(None)
-                    .segment_limit_po2(args.shard_size as u32)
-                    .write_slice(&encoded_input)
-                    .build()
-                    .unwrap()
+        let opts = ProverOpts::default();
+        let prover = get_prover_server(&opts)?;
+
+        Ok(Risc0Evaluator {
+            elf,
+            image_id,
+            segment_limit_po2: args.shard_size as u32,
+            max_cycles: args.max_cycles,
+            input: Vec::new(),
+            executor: None,
+            stdout: GuestOutput::default(),
+            session: None,
+            prover,
+        })
+    }
+
+    fn write_input(&mut self, input: &[Vec<u8>]) -> Result<()> {
+        self.input = input.concat();
+        Ok(())
+    }
+
+    fn prepare_execute(&mut self) -> Result<()> {
+        // Capture the guest output to collect the cycle tracker regions.
+        self.stdout = GuestOutput::default();
+
+        // Raiko blocks can exceed the default session limit, so it is derived from the cycle limit.
+        // The session limit also counts paging and padding cycles, so it leaves them headroom and
+        // the harness checks the cycle limit against the user cycles after execution.
+        let session_limit = self
+            .max_cycles
+            .map(|max_cycles| max_cycles.saturating_mul(SESSION_LIMIT_HEADROOM));
+        let env = ExecutorEnv::builder()
+            .session_limit(session_limit)
+            .segment_limit_po2(self.segment_limit_po2)
+            .write_slice(&self.input)
+            .stdout(self.stdout.clone())
+            .build()?;
+        self.executor = Some(ExecutorImpl::from_elf(env, &self.elf)?);
+        Ok(())
+    }
+
+    fn execute(&mut self) -> Result<Execution> {
+        let mut exec = self
+            .executor
+            .take()
+            .ok_or_else(|| EvalError::Setup("executor must be prepared before executing".into()))?;
+
+        // Generate the session.
+        let session = match (exec.run(), self.max_cycles) {
+            (Ok(session), _) => session,
+            (Err(err), Some(max_cycles)) if err.to_string().contains(SESSION_LIMIT_EXCEEDED) => {
+                return Err(EvalError::CycleLimit(max_cycles).into())
             }
-            _ => ExecutorEnv::builder()
-                .segment_limit_po2(args.shard_size as u32)
-                .build()
-                .unwrap(),
+            (Err(err), _) => return Err(err),
         };
+        let stdout = self.stdout.0.take();
 
-        // Compute some statistics.
-        let mut exec = ExecutorImpl::from_elf(env, &elf).unwrap();
-        // Generate the session.
-        let (session, execution_duration) = time_operation(|| exec.run().unwrap());
-        let cycles = session.user_cycles;
+        let execution = Execution {
+            cycles: Some(session.user_cycles),
+            public_values: session
+                .journal
+                .as_ref()
+                .map(|journal| journal.bytes.clone())
+                .unwrap_or_default(),
+            regions: parse_regions(&String::from_utf8_lossy(&stdout)),
+            // Precompiles such as sha and bigint are invoked through ecalls.
+            syscalls: session
+                .ecall_metrics
+                .iter()
+                .map(|(ecall, metric)| (ecall.clone(), metric.count))
+                .collect(),
+        };
+        self.session = Some(session);
+        Ok(execution)
+    }
 
-        let opts = ProverOpts::default();
-        let prover = get_prover_server(&opts).unwrap();
+    fn prove_core(&mut self) -> Result<StageProof<Self::CoreProof>> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| EvalError::Setup("program must be executed before proving".into()))?;
 
         // Generate the proof.
         let ctx = VerifierContext::default();
-        let (info, core_prove_duration) =
-            time_operation(|| prover.prove_session(&ctx, &session).unwrap());
+        let receipt = self.prover.prove_session(&ctx, session)?.receipt;
 
-        let receipt = info.receipt;
+        // Get the core proof size by summing across all segments.
+        let composite_receipt = receipt.inner.composite()?;
+        let size = composite_receipt
+            .segments
+            .iter()
+            .map(|segment| segment.seal.len() * 4)
+            .sum();
 
-        let composite_receipt = receipt.inner.composite().unwrap();
-        let num_segments = composite_receipt.segments.len();
+        Ok(StageProof {
+            proof: receipt,
+            size: Some(size),
+        })
+    }
 
-        // Get the core proof size by summing across all segments.
-        let mut core_proof_size = 0;
-        for segment in composite_receipt.segments.iter() {
-            core_proof_size += segment.seal.len() * 4;
-        }
+    fn shards(&self, proof: &Self::CoreProof) -> Result<Option<usize>> {
+        Ok(Some(proof.inner.composite()?.segments.len()))
+    }
 
-        // Verify the core proof.
-        let ((), core_verify_duration) = time_operation(|| receipt.verify(image_id).unwrap());
+    fn verify_core(&mut self, proof: &Self::CoreProof) -> Result<()> {
+        proof.verify(self.image_id)?;
+        Ok(())
+    }
 
+    fn compress(
+        &mut self,
+        proof: Self::CoreProof,
+    ) -> Result<Option<StageProof<Self::CompressedProof>>> {
         // Now compress the proof with recursion.
-        let (compressed_proof, compress_duration) =
-            time_operation(|| prover.compress(&ProverOpts::succinct(), &receipt).unwrap());
+        let compressed_proof = self.prover.compress(&ProverOpts::succinct(), &proof)?;
 
-        // Verify the recursive proof
-        let ((), recursive_verify_duration) =
-            time_operation(|| compressed_proof.verify(image_id).unwrap());
+        // Get the recursive proof size.
+        let size = compressed_proof.inner.succinct()?.seal.len() * 4;
+        Ok(Some(StageProof {
+            proof: compressed_proof,
+            size: Some(size),
+        }))
+    }
 
-        let succinct_receipt = compressed_proof.inner.succinct().unwrap();
+    fn verify_compressed(&mut self, proof: &Self::CompressedProof) -> Result<()> {
+        proof.verify(self.image_id)?;
+        Ok(())
+    }
 
-        // GROTH 16 conversion
-        // Bn254 wrapping duration
+    fn wrap(&mut self, proof: Self::CompressedProof) -> Result<Option<Self::WrapProof>> {
+        // Bn254 wrapping.
+        let succinct_receipt = proof.inner.succinct()?;
+        Ok(Some(self.prover.identity_p254(succinct_receipt)?))
+    }
 
-        let (bn254_proof, wrap_prove_duration) =
-            time_operation(|| prover.identity_p254(&succinct_receipt).unwrap());
-        let seal_bytes = bn254_proof.get_seal_bytes();
+    fn groth16(&mut self, proof: &Self::WrapProof) -> Result<Option<usize>> {
+        let seal_bytes = proof.get_seal_bytes();
         tracing::info!("Running groth16 wrapper");
-        let (groth16_proof, groth16_prove_duration) =
-            time_operation(|| risc0_zkvm::stark_to_snark(&seal_bytes).unwrap());
-
+        let groth16_proof = risc0_zkvm::stark_to_snark(&seal_bytes)?;
         tracing::info!("Done running groth16");
 
-        let groth16_proof_size = bincode::serialize(&groth16_proof).unwrap().len();
+        Ok(Some(bincode::serialize(&groth16_proof)?.len()))
+    }
+
+    // TODO(alex): See if risc0 has PLONK out of the box
+}
 
-        // Get the recursive proof size.
-        let recursive_proof_size = succinct_receipt.seal.len() * 4;
-        let prove_duration = core_prove_duration + compress_duration;
-
-        let core_khz = cycles as f64 / core_prove_duration.as_secs_f64() / 1_000.0;
-        let overall_khz = cycles as f64 / prove_duration.as_secs_f64() / 1_000.0;
-
-        // Create the performance report.
-        PerformanceReport {
-            program,
-            prover: args.prover.to_string(),
-            //hashfn: args.hashfn.to_string(),
-            shard_size: args.shard_size,
-            shards: num_segments,
-            cycles: cycles as u64,
-            speed: (cycles as f64) / prove_duration.as_secs_f64(),
-            execution_duration: execution_duration.as_secs_f64(),
-            prove_duration: prove_duration.as_secs_f64(),
-            core_prove_duration: core_prove_duration.as_secs_f64(),
-            core_verify_duration: core_verify_duration.as_secs_f64(),
-            core_proof_size,
-            compress_prove_duration: compress_duration.as_secs_f64(),
-            compress_verify_duration: recursive_verify_duration.as_secs_f64(),
-            compress_proof_size: recursive_proof_size,
-            core_khz,
-            overall_khz,
-            shrink_prove_duration: 0.0,
-            wrap_prove_duration: wrap_prove_duration.as_secs_f64(),
-            groth16_prove_duration: groth16_prove_duration.as_secs_f64(),
-            groth16_proof_size,
-            plonk_prove_duration: 0.0, // TODO(alex): See if risc0 has PLONK out of the box
-            plonk_proof_size: 0,
+/// The factor of the cycle limit the session may run for. Padding each segment to a power of two
+/// can double the cycles, and paging adds to them.
+#[cfg(feature = "risc0")]
+const SESSION_LIMIT_HEADROOM: u64 = 4;
+
+/// The start of the error the executor fails with when the session limit is exceeded.
+#[cfg(feature = "risc0")]
+const SESSION_LIMIT_EXCEEDED: &str = "Session limit exceeded";
+
+/// The prefix of the lines the guest prints for each region as `<prefix> <name> <cycles>`, since
+/// RISC0 has no cycle tracker of its own.
+#[cfg(feature = "risc0")]
+const CYCLE_TRACKER_PREFIX: &str = "cycle-tracker-report:";
+
+/// The guest output, shared by the executor writing it and the evaluator reading it after the
+/// execution.
+#[cfg(feature = "risc0")]
+#[derive(Clone, Default)]
+struct GuestOutput(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(feature = "risc0")]
+impl Write for GuestOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects the cycle tracker regions from the guest output and forwards the remaining lines.
+#[cfg(feature = "risc0")]
+fn parse_regions(stdout: &str) -> Vec<(String, u64)> {
+    let mut regions = Vec::new();
+    for line in stdout.lines() {
+        let region = line
+            .strip_prefix(CYCLE_TRACKER_PREFIX)
+            .and_then(|region| region.trim().rsplit_once(' '))
+            .and_then(|(name, cycles)| Some((name.to_string(), cycles.parse().ok()?)));
+        match region {
+            Some(region) => regions.push(region),
+            None => println!("{line}"),
         }
     }
+    regions
+}
+
+impl Risc0Evaluator {
+    #[cfg(feature = "risc0")]
+    pub fn eval(args: &EvalArgs, reporter: &mut Reporter) -> eyre::Result<()> {
+        evaluator::run::<Self>(args, reporter)
+    }
 
     #[cfg(not(feature = "risc0"))]
-    pub fn eval(_args: &EvalArgs) -> PerformanceReport {
-        panic!("RISC0 feature is not enabled. Please compile with --features risc0");
+    pub fn eval(_args: &EvalArgs, _reporter: &mut Reporter) -> eyre::Result<()> {
+        Err(EvalError::Setup(
+            "RISC0 feature is not enabled. Please compile with --features risc0".into(),
+        )
+        .into())
     }
 }
@@ -1,11 +1,20 @@
 #[cfg(feature = "sp1")]
-use std::fs;
+use std::{fs, path::PathBuf};
+
+#[cfg(feature = "sp1")]
+use anyhow::Result;
+#[cfg(feature = "sp1")]
+use sp1_core_executor::ExecutionError;
+#[cfg(all(feature = "sp1", not(feature = "cuda")))]
+use sp1_core_executor::Program;
+#[cfg(all(feature = "sp1", not(feature = "cuda")))]
+use sp1_prover::DeviceProvingKey;
 #[cfg(feature = "sp1")]
 use sp1_prover::{
-    build, components::CpuProverComponents, utils::get_cycles,
+    build, components::CpuProverComponents, InnerSC, OuterSC, SP1CoreProof, SP1ReduceProof,
 };
 #[cfg(feature = "sp1")]
-use sp1_sdk::{setup_logger, SP1Context, SP1Prover, SP1Stdin};
+use sp1_sdk::{setup_logger, SP1Context, SP1Prover, SP1Stdin, SP1VerifyingKey};
 #[cfg(all(feature = "sp1", not(feature = "cuda")))]
 use sp1_stark::SP1ProverOpts;
 
@@ -13,201 +22,290 @@ use sp1_stark::SP1ProverOpts;
 use sp1_cuda::SP1CudaProver;
 #[cfg(feature = "sp1")]
 use crate::{
-    types::ProgramId,
-    utils::{get_elf, get_raiko_input, get_reth_input, time_operation},
+    evaluator::{self, Execution, StageProof, ZkvmEvaluator},
+    utils::get_elf,
 };
 
-use crate::{EvalArgs, PerformanceReport};
+use crate::{error::EvalError, evaluator::Reporter, EvalArgs};
 
+#[cfg(feature = "sp1")]
+pub struct SP1Evaluator {
+    elf: Vec<u8>,
+    stdin: SP1Stdin,
+    max_cycles: Option<u64>,
+    prover: SP1Prover<CpuProverComponents>,
+    #[cfg(not(feature = "cuda"))]
+    opts: SP1ProverOpts,
+    keys: Option<SP1Keys>,
+    groth16_artifacts_dir: Option<PathBuf>,
+    plonk_artifacts_dir: Option<PathBuf>,
+}
+
+/// The keys generated by [ZkvmEvaluator::setup_prover].
+#[cfg(feature = "sp1")]
+struct SP1Keys {
+    #[cfg(not(feature = "cuda"))]
+    pk_d: DeviceProvingKey<CpuProverComponents>,
+    #[cfg(not(feature = "cuda"))]
+    program: Program,
+    #[cfg(feature = "cuda")]
+    server: SP1CudaProver,
+    vk: SP1VerifyingKey,
+}
+
+#[cfg(not(feature = "sp1"))]
 pub struct SP1Evaluator;
 
-impl SP1Evaluator {
-    #[cfg(feature = "sp1")]
-    pub fn eval(args: &EvalArgs) -> PerformanceReport {
+#[cfg(feature = "sp1")]
+impl ZkvmEvaluator for SP1Evaluator {
+    type CoreProof = SP1CoreProof;
+    type CompressedProof = SP1ReduceProof<InnerSC>;
+    type WrapProof = SP1ReduceProof<OuterSC>;
+
+    fn setup(args: &EvalArgs) -> Result<Self> {
         // Setup the logger.
         setup_logger();
 
-        let program_name = match args.program {
-            ProgramId::Reth | ProgramId::Raiko => format!(
-                "{}_{}",
-                args.program.to_string(),
-                args.block_name.as_deref().unwrap().to_string()
-            ),
-            ProgramId::Fibonacci => format!(
-                "{}_{}",
-                args.program.to_string(),
-                args.fibonacci_input.unwrap().to_string()
-            ),
-            _ => args.program.to_string(),
-        };
-
         // Set enviroment variables to configure the prover.
         std::env::set_var("SHARD_SIZE", format!("{}", 1 << args.shard_size));
-        // if args.program == ProgramId::Reth {
+        // if args.program.name() == "reth" {
         //     std::env::set_var("SHARD_CHUNKING_MULTIPLIER", "4");
         // }
 
-        // Get stdin
-        let stdin = {
-            let mut stdin = SP1Stdin::new();
-            match args.program {
-                ProgramId::Reth => {
-                    let input = get_reth_input(args);
-                    stdin.write_vec(input);
-                }
-                ProgramId::Fibonacci => {
-                    stdin.write(&args.fibonacci_input.expect("missing fibonacci input"));
-                }
-                ProgramId::Raiko => {
-                    let input = get_raiko_input(args);
-                    stdin.write(&input);
-                }
-                _ => (/* NOOP */),
-            }
-            stdin
-        };
-
-        let elf_path = get_elf(args);
-        let elf = fs::read(&elf_path).unwrap();
-
-        let cycles = get_cycles(&elf, &stdin);
+        let elf_path = get_elf(args)?;
+        let elf = fs::read(&elf_path)?;
 
         let prover = SP1Prover::<CpuProverComponents>::new();
 
-        #[cfg(feature = "cuda")]
-        let server = SP1CudaProver::new(None /* gpu_number */).expect("Failed to initialize CUDA prover");
+        Ok(SP1Evaluator {
+            elf,
+            stdin: SP1Stdin::new(),
+            max_cycles: args.max_cycles,
+            prover,
+            // Setup the prover opionts.
+            #[cfg(not(feature = "cuda"))]
+            opts: SP1ProverOpts::auto(),
+            keys: None,
+            groth16_artifacts_dir: None,
+            plonk_artifacts_dir: None,
+        })
+    }
 
+    fn setup_prover(&mut self) -> Result<()> {
         // Setup the program.
         #[cfg(not(feature = "cuda"))]
-        let (_, pk_d, program, vk) = prover.setup(&elf);
+        let (_, pk_d, program, vk) = self.prover.setup(&self.elf);
+
+        #[cfg(feature = "cuda")]
+        let server = SP1CudaProver::new(None /* gpu_number */)
+            .map_err(|err| anyhow::anyhow!("Failed to initialize CUDA prover: {err}"))?;
 
         #[cfg(feature = "cuda")]
-        let (_, vk) = server.setup(&elf).unwrap();
+        let (_, vk) = server.setup(&self.elf)?;
+
+        self.keys = Some(SP1Keys {
+            #[cfg(not(feature = "cuda"))]
+            pk_d,
+            #[cfg(not(feature = "cuda"))]
+            program,
+            #[cfg(feature = "cuda")]
+            server,
+            vk,
+        });
+        Ok(())
+    }
 
-        // Execute the program.
-        let context = SP1Context::default();
-        let (_, execution_duration) =
-            time_operation(|| prover.execute(&elf, &stdin, context.clone()).unwrap());
+    fn write_input(&mut self, input: &[Vec<u8>]) -> Result<()> {
+        let mut stdin = SP1Stdin::new();
+        for buffer in input {
+            stdin.write_vec(buffer.clone());
+        }
+        self.stdin = stdin;
+        Ok(())
+    }
 
-        // Setup the prover opionts.
-        #[cfg(not(feature = "cuda"))]
-        let opts = SP1ProverOpts::auto();
+    fn execute(&mut self) -> Result<Execution> {
+        let mut context = SP1Context::builder();
+        if let Some(max_cycles) = self.max_cycles {
+            context.max_cycles(max_cycles);
+        }
+
+        let (public_values, _, report) =
+            match self.prover.execute(&self.elf, &self.stdin, context.build()) {
+                Ok(execution) => execution,
+                Err(ExecutionError::ExceededCycleLimit(max_cycles)) => {
+                    return Err(EvalError::CycleLimit(max_cycles).into())
+                }
+                Err(err) => return Err(err.into()),
+            };
+        // The cycle tracker regions are reported in no particular order.
+        let mut regions: Vec<_> = report.cycle_tracker.into_iter().collect();
+        regions.sort();
+
+        let syscalls = report
+            .syscall_counts
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(syscall, count)| (format!("{syscall:?}"), *count))
+            .collect();
+
+        Ok(Execution {
+            cycles: Some(report.total_instruction_count()),
+            public_values: public_values.as_slice().to_vec(),
+            regions,
+            syscalls,
+        })
+    }
+
+    fn prove_core(&mut self) -> Result<StageProof<Self::CoreProof>> {
+        let keys = self.keys()?;
 
         // Generate the core proof (CPU).
         #[cfg(not(feature = "cuda"))]
-        let (core_proof, prove_core_duration) = time_operation(|| {
-            prover
-                .prove_core(&pk_d, program, &stdin, opts, context)
-                .unwrap()
-        });
+        let core_proof = self.prover.prove_core(
+            &keys.pk_d,
+            keys.program.clone(),
+            &self.stdin,
+            self.opts,
+            SP1Context::default(),
+        )?;
 
         // Generate the core proof (CUDA).
         #[cfg(feature = "cuda")]
-        let (core_proof, prove_core_duration) =
-            time_operation(|| server.prove_core(&stdin).unwrap());
+        let core_proof = keys.server.prove_core(&self.stdin)?;
 
-        let num_shards = core_proof.proof.0.len();
+        let size = bincode::serialize(&core_proof)?.len();
+        Ok(StageProof {
+            proof: core_proof,
+            size: Some(size),
+        })
+    }
 
-        // Verify the proof.
-        let core_bytes = bincode::serialize(&core_proof).unwrap();
-        let (_, verify_core_duration) = time_operation(|| {
-            prover
-                .verify(&core_proof.proof, &vk)
-                .expect("Proof verification failed")
-        });
+    fn shards(&self, proof: &Self::CoreProof) -> Result<Option<usize>> {
+        Ok(Some(proof.proof.0.len()))
+    }
+
+    fn verify_core(&mut self, proof: &Self::CoreProof) -> Result<()> {
+        self.prover.verify(&proof.proof, &self.keys()?.vk)?;
+        Ok(())
+    }
+
+    fn compress(
+        &mut self,
+        proof: Self::CoreProof,
+    ) -> Result<Option<StageProof<Self::CompressedProof>>> {
+        let keys = self.keys()?;
 
         #[cfg(not(feature = "cuda"))]
-        let (compress_proof, compress_duration) =
-            time_operation(|| prover.compress(&vk, core_proof, vec![], opts).unwrap());
+        let compress_proof = self.prover.compress(&keys.vk, proof, vec![], self.opts)?;
 
         #[cfg(feature = "cuda")]
-        let (compress_proof, compress_duration) =
-            time_operation(|| server.compress(&vk, core_proof, vec![]).unwrap());
+        let compress_proof = keys.server.compress(&keys.vk, proof, vec![])?;
 
-        let compress_bytes = bincode::serialize(&compress_proof).unwrap();
-        println!("recursive proof size: {}", compress_bytes.len());
+        let size = bincode::serialize(&compress_proof)?.len();
+        Ok(Some(StageProof {
+            proof: compress_proof,
+            size: Some(size),
+        }))
+    }
 
-        let (_, verify_compress_duration) = time_operation(|| {
-            prover
-                .verify_compressed(&compress_proof, &vk)
-                .expect("Proof verification failed")
-        });
+    fn verify_compressed(&mut self, proof: &Self::CompressedProof) -> Result<()> {
+        self.prover.verify_compressed(proof, &self.keys()?.vk)?;
+        Ok(())
+    }
 
+    fn shrink(&mut self, proof: &Self::CompressedProof) -> Result<Option<Self::CompressedProof>> {
         #[cfg(not(feature = "cuda"))]
-        let (shrink_proof, shrink_prove_duration) =
-            time_operation(|| prover.shrink(compress_proof.clone(), opts).unwrap());
+        let shrink_proof = self.prover.shrink(proof.clone(), self.opts)?;
 
         #[cfg(feature = "cuda")]
-        let (shrink_proof, shrink_prove_duration) =
-            time_operation(|| server.shrink(compress_proof.clone()).unwrap());
+        let shrink_proof = self.keys()?.server.shrink(proof.clone())?;
 
-        prover
-            .verify_shrink(&shrink_proof, &vk)
-            .expect("Proof verification failed");
+        Ok(Some(shrink_proof))
+    }
+
+    fn verify_shrink(&mut self, proof: &Self::CompressedProof) -> Result<()> {
+        self.prover.verify_shrink(proof, &self.keys()?.vk)?;
+        Ok(())
+    }
 
+    fn wrap(&mut self, proof: Self::CompressedProof) -> Result<Option<Self::WrapProof>> {
         #[cfg(not(feature = "cuda"))]
-        let (wrap_proof, wrap_prove_duration) =
-            time_operation(|| prover.wrap_bn254(shrink_proof.clone(), opts).unwrap());
+        let wrap_proof = self.prover.wrap_bn254(proof, self.opts)?;
 
         #[cfg(feature = "cuda")]
-        let (wrap_proof, wrap_prove_duration) =
-            time_operation(|| server.wrap_bn254(shrink_proof.clone()).unwrap());
+        let wrap_proof = self.keys()?.server.wrap_bn254(proof)?;
 
-        let artifacts_dir =
-            build::try_build_groth16_bn254_artifacts_dev(&wrap_proof.vk, &wrap_proof.proof);
+        Ok(Some(wrap_proof))
+    }
+
+    fn prepare_groth16(&mut self, proof: &Self::WrapProof) -> Result<()> {
+        // The artifacts and warm-up are shared by all trials.
+        if self.groth16_artifacts_dir.is_some() {
+            return Ok(());
+        }
+
+        let artifacts_dir = build::try_build_groth16_bn254_artifacts_dev(&proof.vk, &proof.proof);
 
         // Warm up the prover.
-        prover.wrap_groth16_bn254(wrap_proof.clone(), &artifacts_dir);
+        self.prover.wrap_groth16_bn254(proof.clone(), &artifacts_dir);
 
-        let (groth16_proof, groth16_duration) =
-            time_operation(|| prover.wrap_groth16_bn254(wrap_proof.clone(), &artifacts_dir));
+        self.groth16_artifacts_dir = Some(artifacts_dir);
+        Ok(())
+    }
 
-        let groth16_proof_size = bincode::serialize(&groth16_proof).unwrap().len();
+    fn groth16(&mut self, proof: &Self::WrapProof) -> Result<Option<usize>> {
+        let Some(artifacts_dir) = self.groth16_artifacts_dir.as_ref() else {
+            return Ok(None);
+        };
+        let groth16_proof = self.prover.wrap_groth16_bn254(proof.clone(), artifacts_dir);
+        Ok(Some(bincode::serialize(&groth16_proof)?.len()))
+    }
+
+    fn prepare_plonk(&mut self, proof: &Self::WrapProof) -> Result<()> {
+        // The artifacts and warm-up are shared by all trials.
+        if self.plonk_artifacts_dir.is_some() {
+            return Ok(());
+        }
 
-        let artifacts_dir =
-            build::try_build_plonk_bn254_artifacts_dev(&wrap_proof.vk, &wrap_proof.proof);
+        let artifacts_dir = build::try_build_plonk_bn254_artifacts_dev(&proof.vk, &proof.proof);
 
         // Warm up the prover.
-        prover.wrap_plonk_bn254(wrap_proof.clone(), &artifacts_dir);
-
-        let (plonk_proof, plonk_duration) =
-            time_operation(|| prover.wrap_plonk_bn254(wrap_proof, &artifacts_dir));
-
-        let plonk_proof_size = bincode::serialize(&plonk_proof).unwrap().len();
-
-        let prove_duration = prove_core_duration + compress_duration;
-        let core_khz = cycles as f64 / prove_core_duration.as_secs_f64() / 1_000.0;
-        let overall_khz = cycles as f64 / prove_duration.as_secs_f64() / 1_000.0;
-
-        // Create the performance report.
-        PerformanceReport {
-            program: program_name,
-            prover: args.prover.to_string(),
-            shard_size: args.shard_size,
-            shards: num_shards,
-            cycles: cycles as u64,
-            speed: (cycles as f64) / prove_core_duration.as_secs_f64(),
-            execution_duration: execution_duration.as_secs_f64(),
-            prove_duration: prove_duration.as_secs_f64(),
-            core_prove_duration: prove_core_duration.as_secs_f64(),
-            core_verify_duration: verify_core_duration.as_secs_f64(),
-            core_proof_size: core_bytes.len(),
-            core_khz,
-            compress_prove_duration: compress_duration.as_secs_f64(),
-            compress_verify_duration: verify_compress_duration.as_secs_f64(),
-            compress_proof_size: compress_bytes.len(),
-            overall_khz,
-            shrink_prove_duration: shrink_prove_duration.as_secs_f64(),
-            wrap_prove_duration: wrap_prove_duration.as_secs_f64(),
-            groth16_prove_duration: groth16_duration.as_secs_f64(),
-            groth16_proof_size,
-            plonk_prove_duration: plonk_duration.as_secs_f64(),
-            plonk_proof_size,
-        }
+        self.prover.wrap_plonk_bn254(proof.clone(), &artifacts_dir);
+
+        self.plonk_artifacts_dir = Some(artifacts_dir);
+        Ok(())
+    }
+
+    fn plonk(&mut self, proof: &Self::WrapProof) -> Result<Option<usize>> {
+        let Some(artifacts_dir) = self.plonk_artifacts_dir.as_ref() else {
+            return Ok(None);
+        };
+        let plonk_proof = self.prover.wrap_plonk_bn254(proof.clone(), artifacts_dir);
+        Ok(Some(bincode::serialize(&plonk_proof)?.len()))
+    }
+}
+
+#[cfg(feature = "sp1")]
+impl SP1Evaluator {
+    fn keys(&self) -> Result<&SP1Keys, EvalError> {
+        self.keys
+            .as_ref()
+            .ok_or_else(|| EvalError::Setup("prover must be set up before proving".into()))
+    }
+}
+
+impl SP1Evaluator {
+    #[cfg(feature = "sp1")]
+    pub fn eval(args: &EvalArgs, reporter: &mut Reporter) -> eyre::Result<()> {
+        evaluator::run::<Self>(args, reporter)
     }
 
     #[cfg(not(feature = "sp1"))]
-    pub fn eval(_args: &EvalArgs) -> PerformanceReport {
-        panic!("SP1 feature is not enabled. Please compile with --features sp1");
+    pub fn eval(_args: &EvalArgs, _reporter: &mut Reporter) -> eyre::Result<()> {
+        Err(EvalError::Setup(
+            "SP1 feature is not enabled. Please compile with --features sp1".into(),
+        )
+        .into())
     }
 }
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{utils::Profile, PerformanceReport};
+
+/// A statistic aggregating a measurement over several trials.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Statistic {
+    Mean,
+    Median,
+    Stddev,
+    Min,
+    Max,
+}
+
+impl Statistic {
+    pub const ALL: [Statistic; 5] = [
+        Statistic::Mean,
+        Statistic::Median,
+        Statistic::Stddev,
+        Statistic::Min,
+        Statistic::Max,
+    ];
+
+    /// Computes the statistic over a non-empty set of samples.
+    pub fn compute(&self, samples: &[f64]) -> f64 {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        match self {
+            Statistic::Mean => mean,
+            Statistic::Median => {
+                let mut sorted = samples.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                if sorted.len() % 2 == 1 {
+                    sorted[mid]
+                } else {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                }
+            }
+            Statistic::Stddev => {
+                if samples.len() < 2 {
+                    return 0.0;
+                }
+                // Sample standard deviation.
+                let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+                variance.sqrt()
+            }
+            Statistic::Min => samples.iter().copied().fold(f64::INFINITY, f64::min),
+            Statistic::Max => samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// Aggregates the reports of several trials into one report per [Statistic].
+///
+/// Durations, the speeds derived from them, peak memory, CPU usage and the profiles of the stages
+/// are aggregated, everything else is taken from the first trial. A single trial is returned as is.
+pub fn aggregate(trials: Vec<PerformanceReport>) -> Vec<PerformanceReport> {
+    if trials.len() == 1 {
+        return trials;
+    }
+
+    let first = &trials[0];
+
+    Statistic::ALL
+        .iter()
+        .map(|statistic| {
+            let aggregate = |field: fn(&PerformanceReport) -> Option<f64>| {
+                let samples: Vec<f64> = trials.iter().filter_map(field).collect();
+                (!samples.is_empty()).then(|| statistic.compute(&samples))
+            };
+
+            PerformanceReport {
+                statistic: Some(*statistic),
+                speed: aggregate(|r| r.speed),
+                execution_duration: aggregate(|r| Some(r.execution_duration)).unwrap_or_default(),
+                prove_duration: aggregate(|r| r.prove_duration),
+                core_prove_duration: aggregate(|r| r.core_prove_duration),
+                core_verify_duration: aggregate(|r| r.core_verify_duration),
+                compress_prove_duration: aggregate(|r| r.compress_prove_duration),
+                compress_verify_duration: aggregate(|r| r.compress_verify_duration),
+                core_khz: aggregate(|r| r.core_khz),
+                overall_khz: aggregate(|r| r.overall_khz),
+                shrink_prove_duration: aggregate(|r| r.shrink_prove_duration),
+                wrap_prove_duration: aggregate(|r| r.wrap_prove_duration),
+                groth16_prove_duration: aggregate(|r| r.groth16_prove_duration),
+                plonk_prove_duration: aggregate(|r| r.plonk_prove_duration),
+                peak_rss: aggregate(|r| r.peak_rss.map(|rss| rss as f64)).map(|rss| rss as u64),
+                cpu_utilisation: aggregate(|r| r.cpu_utilisation),
+                user_time: aggregate(|r| r.user_time),
+                system_time: aggregate(|r| r.system_time),
+                stage_profiles: aggregate_profiles(&trials, *statistic),
+                ..first.clone()
+            }
+        })
+        .collect()
+}
+
+/// Aggregates the profile of each stage over the trials that ran it.
+fn aggregate_profiles(
+    trials: &[PerformanceReport],
+    statistic: Statistic,
+) -> Vec<(&'static str, Profile)> {
+    trials[0]
+        .stage_profiles
+        .iter()
+        .map(|(stage, _)| {
+            let profiles: Vec<&Profile> = trials
+                .iter()
+                .flat_map(|trial| &trial.stage_profiles)
+                .filter(|(trial_stage, _)| trial_stage == stage)
+                .map(|(_, profile)| profile)
+                .collect();
+            let aggregate = |field: fn(&Profile) -> Option<f64>| {
+                let samples: Vec<f64> = profiles.iter().copied().filter_map(field).collect();
+                (!samples.is_empty()).then(|| statistic.compute(&samples))
+            };
+
+            let profile = Profile {
+                duration: Duration::from_secs_f64(
+                    aggregate(|p| Some(p.duration.as_secs_f64())).unwrap_or_default(),
+                ),
+                peak_rss: aggregate(|p| p.peak_rss.map(|rss| rss as f64)).map(|rss| rss as u64),
+                user_time: aggregate(|p| p.user_time),
+                system_time: aggregate(|p| p.system_time),
+            };
+            (*stage, profile)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [f64; 8] = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn computes_statistics() {
+        assert_close(Statistic::Mean.compute(&SAMPLES), 5.0);
+        assert_close(Statistic::Median.compute(&SAMPLES), 4.5);
+        assert_close(Statistic::Stddev.compute(&SAMPLES), (32.0f64 / 7.0).sqrt());
+        assert_close(Statistic::Min.compute(&SAMPLES), 2.0);
+        assert_close(Statistic::Max.compute(&SAMPLES), 9.0);
+    }
+
+    #[test]
+    fn median_of_odd_samples_is_the_middle_one() {
+        assert_close(Statistic::Median.compute(&[9.0, 1.0, 3.0]), 3.0);
+    }
+
+    #[test]
+    fn computes_statistics_of_a_single_sample() {
+        for statistic in [
+            Statistic::Mean,
+            Statistic::Median,
+            Statistic::Min,
+            Statistic::Max,
+        ] {
+            assert_close(statistic.compute(&[3.5]), 3.5);
+        }
+        assert_close(Statistic::Stddev.compute(&[3.5]), 0.0);
+    }
+
+    #[test]
+    fn keeps_a_single_trial() {
+        let trial = PerformanceReport {
+            execution_duration: 1.5,
+            ..Default::default()
+        };
+        let reports = aggregate(vec![trial]);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].statistic, None);
+        assert_close(reports[0].execution_duration, 1.5);
+    }
+
+    #[test]
+    fn aggregates_trials() {
+        let trials = [1.0, 3.0, 2.0]
+            .into_iter()
+            .map(|duration| PerformanceReport {
+                cycles: Some(100),
+                execution_duration: duration,
+                peak_rss: Some(duration as u64 * 1000),
+                core_prove_duration: Some(duration * 10.0),
+                ..Default::default()
+            })
+            .collect();
+        let reports = aggregate(trials);
+
+        let statistics: Vec<_> = reports.iter().map(|report| report.statistic).collect();
+        assert_eq!(statistics, Statistic::ALL.map(Some));
+        let median = &reports[1];
+        assert_eq!(median.cycles, Some(100));
+        assert_close(median.execution_duration, 2.0);
+        assert_close(median.core_prove_duration.unwrap(), 20.0);
+        assert_eq!(median.peak_rss, Some(2000));
+        assert_eq!(reports[4].peak_rss, Some(3000));
+        // Stages that did not run stay empty.
+        assert_eq!(median.compress_prove_duration, None);
+    }
+
+    #[test]
+    fn aggregates_stage_profiles() {
+        let trials = [1.0, 3.0]
+            .into_iter()
+            .map(|duration| PerformanceReport {
+                stage_profiles: vec![(
+                    "execute",
+                    Profile {
+                        duration: Duration::from_secs_f64(duration),
+                        peak_rss: Some(duration as u64 * 1000),
+                        user_time: Some(duration / 2.0),
+                        system_time: None,
+                    },
+                )],
+                ..Default::default()
+            })
+            .collect();
+        let reports = aggregate(trials);
+
+        let (stage, mean) = &reports[0].stage_profiles[0];
+        assert_eq!(*stage, "execute");
+        assert_close(mean.duration.as_secs_f64(), 2.0);
+        assert_eq!(mean.peak_rss, Some(2000));
+        assert_close(mean.user_time.unwrap(), 1.0);
+        assert_eq!(mean.system_time, None);
+        let (_, max) = &reports[4].stage_profiles[0];
+        assert_close(max.duration.as_secs_f64(), 3.0);
+    }
+}
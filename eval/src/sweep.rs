@@ -0,0 +1,462 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    fs::{self, create_dir_all, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use block_manifest::BlockFormat;
+use clap::{Parser, ValueEnum};
+use eyre::{bail, eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::EvalError,
+    output::{write_results, RunMetadata},
+    programs::{self, BenchmarkProgram},
+    types::{OutputFormat, ProverId, Stage},
+    utils::list_blocks,
+    EvalArgs, PerformanceReport,
+};
+
+/// A matrix of programs, provers, shard sizes and inputs to evaluate, read from a TOML or JSON
+/// file.
+///
+/// Blocks default to all the blocks that were downloaded.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Matrix {
+    /// The results file all runs are written to.
+    pub filename: String,
+    pub programs: Vec<String>,
+    pub provers: Vec<String>,
+    pub shard_sizes: Vec<u64>,
+    /// The inputs of the `fibonacci` program.
+    #[serde(default)]
+    pub fibonacci_inputs: Vec<u32>,
+    /// The blocks in `eval/blocks` to run the `reth` program on.
+    pub reth_blocks: Option<Vec<String>>,
+    /// The suffix of the `eval/blocks-taiko_<suffix>` directory to read `raiko` blocks from.
+    pub taiko_blocks_dir_suffix: Option<String>,
+    /// The blocks to run the `raiko` program on.
+    pub taiko_blocks: Option<Vec<String>>,
+    /// The stages to run, all of them by default.
+    pub stages: Option<Vec<String>>,
+    #[serde(default = "default_trials")]
+    pub trials: u32,
+    #[serde(default)]
+    pub warmup: u32,
+    pub format: Option<String>,
+    /// The maximum number of cycles a program may execute.
+    pub max_cycles: Option<u64>,
+    /// The wall-clock timeout of each stage in seconds, so a run that hangs does not stop the
+    /// sweep.
+    pub stage_timeout: Option<u64>,
+}
+
+fn default_trials() -> u32 {
+    1
+}
+
+/// One combination of the matrix, run by a child process.
+struct Run {
+    program: &'static dyn BenchmarkProgram,
+    prover: ProverId,
+    shard_size: u64,
+    input: RunInput,
+}
+
+#[derive(Clone)]
+enum RunInput {
+    None,
+    Fibonacci(u32),
+    Block(String),
+}
+
+impl Run {
+    fn describe(&self) -> String {
+        let input = match &self.input {
+            RunInput::None => String::new(),
+            RunInput::Fibonacci(input) => format!(" input {input}"),
+            RunInput::Block(block) => format!(" block {block}"),
+        };
+        format!(
+            "{} on {} with shard size {}{}",
+            self.program, self.prover, self.shard_size, input
+        )
+    }
+}
+
+impl Matrix {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(&contents)?),
+            Some("json") => Ok(serde_json::from_str(&contents)?),
+            _ => bail!("Matrix file {} must be TOML or JSON", path.display()),
+        }
+    }
+
+    /// Checks the matrix and expands it into the runs, in order.
+    fn runs(&self) -> Result<Vec<Run>> {
+        let programs = self
+            .programs
+            .iter()
+            .map(|name| programs::parse_program(name).map_err(|err| eyre!(err)))
+            .collect::<Result<Vec<_>>>()?;
+        let provers = self
+            .provers
+            .iter()
+            .map(|name| ProverId::from_str(name, true).map_err(|err| eyre!(err)))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(stages) = &self.stages {
+            for stage in stages {
+                Stage::from_str(stage, true).map_err(|err| eyre!(err))?;
+            }
+        }
+        if let Some(format) = &self.format {
+            OutputFormat::from_str(format, true).map_err(|err| eyre!(err))?;
+        }
+
+        // The runs are child processes of this binary, so it needs every prover.
+        for prover in &provers {
+            if !prover.enabled() {
+                bail!(
+                    "The {prover} feature is not enabled. Please compile with --features {prover}"
+                );
+            }
+        }
+
+        let mut runs = Vec::new();
+        for program in &programs {
+            let inputs = self.inputs(*program)?;
+            for prover in &provers {
+                for shard_size in &self.shard_sizes {
+                    for input in &inputs {
+                        runs.push(Run {
+                            program: *program,
+                            prover: prover.clone(),
+                            shard_size: *shard_size,
+                            input: input.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(runs)
+    }
+
+    /// The inputs a program is run with.
+    fn inputs(&self, program: &dyn BenchmarkProgram) -> Result<Vec<RunInput>> {
+        let inputs = match program.name() {
+            "fibonacci" => {
+                if self.fibonacci_inputs.is_empty() {
+                    bail!("The fibonacci program needs fibonacci_inputs");
+                }
+                self.fibonacci_inputs
+                    .iter()
+                    .copied()
+                    .map(RunInput::Fibonacci)
+                    .collect()
+            }
+            "reth" => match &self.reth_blocks {
+                Some(blocks) => blocks.clone(),
+                None => list_blocks(Path::new("eval/blocks"), &["bin"])?,
+            }
+            .into_iter()
+            .map(RunInput::Block)
+            .collect(),
+            "raiko" => {
+                let Some(suffix) = &self.taiko_blocks_dir_suffix else {
+                    bail!("The raiko program needs taiko_blocks_dir_suffix");
+                };
+                let blocks_dir = PathBuf::from(format!("eval/blocks-taiko_{suffix}"));
+                match &self.taiko_blocks {
+                    Some(blocks) => blocks.clone(),
+                    None => list_blocks(&blocks_dir, BlockFormat::EXTENSIONS)?,
+                }
+                .into_iter()
+                .map(RunInput::Block)
+                .collect()
+            }
+            _ => vec![RunInput::None],
+        };
+        Ok(inputs)
+    }
+
+    /// The arguments of the child process that evaluates a run.
+    fn args(&self, run: &Run) -> Vec<String> {
+        let mut args = vec![
+            format!("--program={}", run.program),
+            format!("--prover={}", run.prover),
+            format!("--shard-size={}", run.shard_size),
+            format!("--filename={}", self.filename),
+            format!("--trials={}", self.trials),
+            format!("--warmup={}", self.warmup),
+        ];
+        match &run.input {
+            RunInput::None => {}
+            RunInput::Fibonacci(input) => args.push(format!("--fibonacci-input={input}")),
+            RunInput::Block(block) => args.push(format!("--block-name={block}")),
+        }
+        if let Some(suffix) = &self.taiko_blocks_dir_suffix {
+            args.push(format!("--taiko-blocks-dir-suffix={suffix}"));
+        }
+        if let Some(stages) = &self.stages {
+            args.push(format!("--stages={}", stages.join(",")));
+        }
+        if let Some(format) = &self.format {
+            args.push(format!("--format={format}"));
+        }
+        if let Some(max_cycles) = self.max_cycles {
+            args.push(format!("--max-cycles={max_cycles}"));
+        }
+        if let Some(stage_timeout) = self.stage_timeout {
+            args.push(format!("--stage-timeout={stage_timeout}"));
+        }
+        args
+    }
+}
+
+/// The combination of a run, as it is recorded in the sweep state file.
+///
+/// Unlike the other fields, the trials are not recorded one by one: the trials of a combination run
+/// in one process and are aggregated into one set of statistics, so a combination is recorded with
+/// its number of trials once all of them have run, and is run again when the number changes.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash)]
+struct RunKey {
+    program: String,
+    prover: String,
+    shard_size: u64,
+    input: Option<String>,
+    /// State files written before the trials were recorded ran the default number of trials.
+    #[serde(default = "default_trials")]
+    trials: u32,
+}
+
+/// A line of the sweep state file.
+#[derive(Serialize, Deserialize)]
+struct RunState {
+    #[serde(flatten)]
+    key: RunKey,
+    error: Option<String>,
+}
+
+impl Run {
+    fn key(&self, trials: u32) -> RunKey {
+        RunKey {
+            program: self.program.name().to_string(),
+            prover: self.prover.to_string(),
+            shard_size: self.shard_size,
+            input: match &self.input {
+                RunInput::None => None,
+                RunInput::Fibonacci(input) => Some(input.to_string()),
+                RunInput::Block(block) => Some(block.clone()),
+            },
+            trials,
+        }
+    }
+}
+
+/// Reads the runs recorded in the state file, along with whether they failed.
+fn read_state(path: &Path) -> Result<HashMap<RunKey, bool>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let mut state = HashMap::new();
+    for line in fs::read_to_string(path)?.lines() {
+        // The last line may be incomplete if the sweep was killed while writing it.
+        match serde_json::from_str::<RunState>(line) {
+            Ok(run) => {
+                state.insert(run.key, run.error.is_some());
+            }
+            Err(err) => println!("Ignoring invalid line in {}: {err}", path.display()),
+        }
+    }
+    Ok(state)
+}
+
+fn append_state(path: &Path, run: &Run, trials: u32, error: Option<String>) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let state = RunState {
+        key: run.key(trials),
+        error,
+    };
+    writeln!(file, "{}", serde_json::to_string(&state)?)?;
+    Ok(())
+}
+
+/// Why the child process of a run failed.
+struct Failure {
+    error: String,
+    /// Whether the child recorded the failed run in the results itself, which it does when it
+    /// exits with the code of an [EvalError].
+    recorded: bool,
+}
+
+/// Builds the guest of a program for a prover, forwarding the output of `build.sh`, and returns
+/// the toolchain it was built with.
+fn build_guest(program: &str, prover: &str) -> Result<Option<String>> {
+    let mut build = Command::new("bash")
+        .args(["build.sh", program, prover])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut toolchain = None;
+    let stdout = BufReader::new(build.stdout.take().expect("stdout is piped"));
+    for line in stdout.lines() {
+        let line = line?;
+        match line.strip_prefix("GUEST_TOOLCHAIN=") {
+            Some(reported) => toolchain = Some(reported.to_string()),
+            None => println!("{line}"),
+        }
+    }
+
+    if !build.wait()?.success() {
+        bail!("Failed to build {program} for {prover}");
+    }
+    Ok(toolchain.filter(|toolchain| !toolchain.is_empty()))
+}
+
+/// Runs the child process of a run, forwarding its output, and returns why it failed, if it did.
+/// The guest toolchain is passed on for the run metadata.
+fn run_child(
+    exe: &Path,
+    args: &[String],
+    guest_toolchain: Option<&str>,
+) -> Result<Option<Failure>> {
+    let mut command = Command::new(exe);
+    command.args(args).stderr(Stdio::piped());
+    if let Some(guest_toolchain) = guest_toolchain {
+        command.env("GUEST_TOOLCHAIN", guest_toolchain);
+    }
+    let mut child = command.spawn()?;
+
+    // Keep the panic message, or else the last line, to describe the failure.
+    let mut panic_message = None;
+    let mut last_line = None;
+    let mut panicked = false;
+    let stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+    for line in stderr.lines() {
+        let line = line?;
+        eprintln!("{line}");
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if panicked {
+            panic_message = Some(line.to_string());
+            panicked = false;
+        } else if line.contains("panicked at") {
+            panicked = true;
+        }
+        // Failed evaluations print their error last.
+        last_line = Some(line.strip_prefix("Error: ").unwrap_or(line).to_string());
+    }
+    let message = panic_message.or(last_line);
+
+    let status = child.wait()?;
+    if status.success() {
+        return Ok(None);
+    }
+    let error = match message {
+        Some(message) => format!("{message} ({status})"),
+        None => status.to_string(),
+    };
+    Ok(Some(Failure {
+        error,
+        recorded: status.code().is_some_and(EvalError::is_exit_code),
+    }))
+}
+
+/// Writes the row of a failed run to the results of the sweep.
+fn write_failure(matrix: &Matrix, run: &Run, error: String) -> Result<()> {
+    let args =
+        ChildArgs::try_parse_from(std::iter::once("eval".to_string()).chain(matrix.args(run)))?
+            .eval;
+    let report = PerformanceReport::failure(&args, error, None);
+    write_results(
+        &args.filename,
+        args.format,
+        &RunMetadata::new(&args),
+        &[report],
+    )
+}
+
+/// The arguments of a child process, parsed in the sweep to describe failed runs.
+#[derive(Parser)]
+struct ChildArgs {
+    #[command(flatten)]
+    eval: EvalArgs,
+}
+
+/// Builds every guest of the matrix once and evaluates each combination in a child process. All
+/// runs append to the same results file.
+pub fn sweep(matrix_path: &Path, retry_failed: bool) -> Result<()> {
+    let matrix = Matrix::read(matrix_path)?;
+
+    // Skip the runs that completed in a previous sweep.
+    let state_path =
+        PathBuf::from("results").join(format!("{}_sweep_state.ndjson", matrix.filename));
+    create_dir_all("results")?;
+    let state = read_state(&state_path)?;
+    let runs: Vec<_> = matrix
+        .runs()?
+        .into_iter()
+        .filter(|run| match state.get(&run.key(matrix.trials)) {
+            Some(failed) => *failed && retry_failed,
+            None => true,
+        })
+        .collect();
+    if !state.is_empty() {
+        println!(
+            "Resuming sweep from {}, {} runs left",
+            state_path.display(),
+            runs.len()
+        );
+    }
+
+    // Build each guest once, with the same script as `eval.sh`.
+    let guests: BTreeSet<_> = runs
+        .iter()
+        .map(|run| (run.program.name(), run.prover.to_string()))
+        .collect();
+    let mut toolchains = HashMap::new();
+    for (program, prover) in guests {
+        println!("Building {program} for {prover}");
+        if let Some(toolchain) = build_guest(program, &prover)? {
+            toolchains.insert((program, prover), toolchain);
+        }
+    }
+
+    let exe = env::current_exe()?;
+    let mut failures = 0;
+    for (index, run) in runs.iter().enumerate() {
+        println!("Running {}/{}: {}", index + 1, runs.len(), run.describe());
+        let toolchain = toolchains.get(&(run.program.name(), run.prover.to_string()));
+        let failure = run_child(&exe, &matrix.args(run), toolchain.map(String::as_str))?;
+        if let Some(failure) = &failure {
+            println!("Run failed: {}: {}", run.describe(), failure.error);
+            // Crashed runs did not get to record their failure.
+            if !failure.recorded {
+                write_failure(&matrix, run, failure.error.clone())?;
+            }
+            failures += 1;
+        }
+        append_state(
+            &state_path,
+            run,
+            matrix.trials,
+            failure.map(|failure| failure.error),
+        )?;
+    }
+
+    if failures > 0 {
+        println!("{failures} of {} runs failed", runs.len());
+    }
+
+    Ok(())
+}
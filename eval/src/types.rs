@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use clap::ValueEnum;
+use serde::Serialize;
 
 /// An identifier used to select the prover to evaluate.
 #[derive(ValueEnum, Clone, PartialEq)]
@@ -8,6 +9,31 @@ pub enum ProverId {
     SP1,
     Jolt,
     Nexus,
+    /// Runs the guest logic directly on the host, as a baseline for the zkVMs.
+    Native,
+}
+
+impl ProverId {
+    /// The target triple the guest ELF is built for, if the prover loads an ELF.
+    pub fn target_triple(&self) -> Option<&'static str> {
+        match self {
+            ProverId::SP1 => Some("riscv32im-succinct-zkvm-elf"),
+            ProverId::Risc0 => Some("riscv32im-risc0-zkvm-elf"),
+            ProverId::Nexus => Some("riscv32i-unknown-none-elf"),
+            ProverId::Jolt | ProverId::Native => None,
+        }
+    }
+
+    /// Whether the feature of the prover is compiled in.
+    pub fn enabled(&self) -> bool {
+        match self {
+            ProverId::Risc0 => cfg!(feature = "risc0"),
+            ProverId::SP1 => cfg!(feature = "sp1"),
+            ProverId::Jolt => cfg!(feature = "jolt"),
+            ProverId::Nexus => cfg!(feature = "nexus"),
+            ProverId::Native => cfg!(feature = "native"),
+        }
+    }
 }
 
 impl Display for ProverId {
@@ -17,6 +43,45 @@ impl Display for ProverId {
             ProverId::SP1 => write!(f, "sp1"),
             ProverId::Jolt => write!(f, "jolt"),
             ProverId::Nexus => write!(f, "nexus"),
+            ProverId::Native => write!(f, "native"),
+        }
+    }
+}
+
+/// A stage of the evaluation that can be selected with `--stages`.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Execute the program and record its cycle count.
+    Execute,
+    /// Generate and verify the core proof.
+    Core,
+    /// Compress the core proof with recursion.
+    Compress,
+    /// Wrap the compressed proof into a Groth16 proof.
+    Groth16,
+    /// Wrap the compressed proof into a PLONK proof.
+    Plonk,
+}
+
+/// The format results are written in, selected with `--format`.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One row per report, appended to a CSV file.
+    Csv,
+    /// A JSON array of records carrying the schema version and run metadata.
+    Json,
+    /// One JSON record per line, like `json`.
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// The extension of the results file.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
         }
     }
 }
@@ -41,26 +106,3 @@ impl Display for ProverId {
 //         }
 //     }
 // }
-
-/// An identifier used to select the program to evaluate.
-#[derive(ValueEnum, Clone, PartialEq)]
-#[clap(rename_all = "kebab_case")]
-pub enum ProgramId {
-    Loop,
-    Fibonacci,
-    Tendermint,
-    Reth,
-    Raiko
-}
-
-impl Display for ProgramId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ProgramId::Loop => write!(f, "loop"),
-            ProgramId::Fibonacci => write!(f, "fibonacci"),
-            ProgramId::Tendermint => write!(f, "tendermint"),
-            ProgramId::Reth => write!(f, "reth"),
-            ProgramId::Raiko => write!(f, "raiko"),
-        }
-    }
-}
@@ -1,67 +1,119 @@
 use std::{
     env, fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
     time::{Duration, Instant},
 };
+use block_manifest::{find_block, BlockFormat, MANIFEST_FILE};
 use raiko_lib::input::GuestInput;
 
-use crate::{
-    types::{ProgramId, ProverId},
-    EvalArgs,
-};
+use crate::{block_format, error::EvalError, EvalArgs};
 
-pub fn get_elf(args: &EvalArgs) -> String {
-    let mut program_dir = args.program.to_string();
-    if args.program == ProgramId::Tendermint || args.program == ProgramId::Reth || args.program == ProgramId::Raiko {
-        program_dir += "-";
-        program_dir += args.prover.to_string().as_str();
+pub fn get_elf(args: &EvalArgs) -> Result<PathBuf, EvalError> {
+    let elf_path = args.program.elf_path(&args.prover)?;
+    if !elf_path.exists() {
+        return Err(EvalError::ElfNotBuilt(elf_path));
     }
 
-    let current_dir = env::current_dir().expect("Failed to get current working directory");
+    println!("elf path: {}", elf_path.display());
+    Ok(elf_path)
+}
 
-    let target_name = match args.prover {
-        ProverId::SP1 => "riscv32im-succinct-zkvm-elf",
-        ProverId::Risc0 => "riscv32im-risc0-zkvm-elf",
-        ProverId::Nexus => "riscv32i-unknown-none-elf",
-        _ => panic!("prover not supported"),
-    };
+/// The directory of `reth` blocks, `eval/blocks` unless `--blocks-dir` is given.
+pub fn reth_blocks_dir(args: &EvalArgs) -> Result<PathBuf, EvalError> {
+    match &args.blocks_dir {
+        Some(blocks_dir) => Ok(blocks_dir.clone()),
+        None => eval_dir("blocks"),
+    }
+}
+
+/// The directory of `raiko` blocks, `eval/blocks-taiko_<suffix>` unless `--blocks-dir` is given.
+pub fn raiko_blocks_dir(args: &EvalArgs) -> Result<PathBuf, EvalError> {
+    if let Some(blocks_dir) = &args.blocks_dir {
+        return Ok(blocks_dir.clone());
+    }
+    let dir_suffix = args
+        .taiko_blocks_dir_suffix
+        .as_deref()
+        .ok_or_else(|| EvalError::MissingInput("taiko_blocks_dir_suffix not provided".into()))?;
+    eval_dir(&format!("blocks-taiko_{dir_suffix}"))
+}
 
-    let elf_path = current_dir.join(format!(
-        "benchmarks/{}/target/{}/release/{}",
-        program_dir, target_name, program_dir
-    ));
+fn eval_dir(name: &str) -> Result<PathBuf, EvalError> {
+    let current_dir = env::current_dir()
+        .map_err(|e| EvalError::Setup(format!("Failed to get current working directory: {e}")))?;
+    Ok(current_dir.join("eval").join(name))
+}
 
-    let elf_path_str = elf_path
-        .to_str()
-        .expect("Failed to convert path to string")
-        .to_string();
-    println!("elf path: {}", elf_path_str);
-    elf_path_str
+pub fn get_reth_input(args: &EvalArgs) -> Result<Vec<u8>, EvalError> {
+    let block_name = block_name(args)?;
+    read_block(&reth_blocks_dir(args)?, block_name, "bin")
 }
 
-pub fn get_reth_input(args: &EvalArgs) -> Vec<u8> {
-    let block_name = args.block_name.as_deref().expect("Block name is required for Reth program");
-    read_block("blocks", block_name, "bin")
+/// Reads the `GuestInput` of a `raiko` block, stored in any of the [BlockFormat]s.
+pub fn get_raiko_input(args: &EvalArgs) -> Result<GuestInput, EvalError> {
+    let block_name = block_name(args)?;
+    let blocks_dir = raiko_blocks_dir(args)?;
+    let Some((file_path, format)) = find_block(&blocks_dir, block_name) else {
+        return Err(EvalError::MissingInput(format!(
+            "No block file for {block_name} in {} (tried {})",
+            blocks_dir.display(),
+            BlockFormat::EXTENSIONS.join(", ")
+        )));
+    };
+    let guest_input = read_block_file(&file_path)?;
+    block_format::decode(format, &guest_input).map_err(|e| {
+        EvalError::MissingInput(format!(
+            "Failed to parse guest input of block {block_name}: {e:#}"
+        ))
+    })
 }
 
-pub fn get_raiko_input(args: &EvalArgs) -> GuestInput {
-    let dir_suffix = args.taiko_blocks_dir_suffix.as_deref().expect("taiko_blocks_dir_suffix not provided");
-    let block_name = args.block_name.as_deref().expect("block_name not provided");
-    let guest_input_json = String::from_utf8(read_block(&format!("blocks-taiko_{dir_suffix}"), block_name, "json")).unwrap();
-    serde_json::from_str(&guest_input_json).expect("Failed to parse guest input JSON")
+fn block_name(args: &EvalArgs) -> Result<&str, EvalError> {
+    args.block_name
+        .as_deref()
+        .ok_or_else(|| EvalError::MissingInput("block_name not provided".into()))
 }
 
-pub fn read_block(blocks_dir_name: &str, block_name: &str, ext: &str) -> Vec<u8> {
-    let current_dir = env::current_dir().expect("Failed to get current working directory");
-    let blocks_dir = current_dir.join("eval").join(blocks_dir_name);
-    let file_path = blocks_dir.join(format!("{block_name}.{ext}"));
+pub fn read_block(blocks_dir: &Path, block_name: &str, ext: &str) -> Result<Vec<u8>, EvalError> {
+    read_block_file(&blocks_dir.join(format!("{block_name}.{ext}")))
+}
 
-    match fs::read(&file_path) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            tracing::error!("Failed to read block file: {:?}", e);
-            panic!("Unable to read block file: {:?}", e);
-        }
-    }
+/// Reads a block file, checking it against the manifest of its directory.
+fn read_block_file(file_path: &Path) -> Result<Vec<u8>, EvalError> {
+    let bytes = fs::read(file_path).map_err(|e| {
+        EvalError::MissingInput(format!(
+            "Unable to read block file {}: {e}",
+            file_path.display()
+        ))
+    })?;
+    block_manifest::verify(file_path, &bytes)?;
+    Ok(bytes)
+}
+
+/// Lists the names of the blocks in a directory with any of the given extensions, sorted and
+/// without duplicates.
+pub fn list_blocks(blocks_dir: &Path, extensions: &[&str]) -> Result<Vec<String>, EvalError> {
+    let mut blocks: Vec<String> = fs::read_dir(blocks_dir)
+        .map_err(|e| {
+            EvalError::MissingInput(format!("Unable to read {}: {e}", blocks_dir.display()))
+        })?
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            if file_name == MANIFEST_FILE {
+                return None;
+            }
+            extensions.iter().find_map(|ext| {
+                let block = file_name.strip_suffix(ext)?.strip_suffix('.')?;
+                Some(block.to_string())
+            })
+        })
+        .collect();
+    // Block numbers sort numerically.
+    blocks.sort_by_key(|block| (block.parse::<u64>().ok(), block.clone()));
+    blocks.dedup();
+    Ok(blocks)
 }
 
 pub fn time_operation<T, F: FnOnce() -> T>(operation: F) -> (T, Duration) {
@@ -70,3 +122,85 @@ pub fn time_operation<T, F: FnOnce() -> T>(operation: F) -> (T, Duration) {
     let duration = start.elapsed();
     (result, duration)
 }
+
+/// The interval at which the resident set size is sampled while profiling.
+const RSS_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The duration and resource usage of an operation.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub duration: Duration,
+    /// The peak resident set size of the process in bytes, sampled while the operation ran.
+    pub peak_rss: Option<u64>,
+    /// The CPU time spent in user mode in seconds.
+    pub user_time: Option<f64>,
+    /// The CPU time spent in kernel mode in seconds.
+    pub system_time: Option<f64>,
+}
+
+impl Profile {
+    /// The average number of cores the operation kept busy.
+    pub fn cpu_utilisation(&self) -> Option<f64> {
+        Some((self.user_time? + self.system_time?) / self.duration.as_secs_f64())
+    }
+}
+
+/// Times an operation while sampling the resource usage of the process from `/proc/self`.
+pub fn profile_operation<T, F: FnOnce() -> T>(operation: F) -> (T, Profile) {
+    let done = AtomicBool::new(false);
+    let start_times = cpu_times();
+
+    let (result, duration, peak_rss) = thread::scope(|scope| {
+        let sampler = scope.spawn(|| {
+            let mut peak_rss = rss();
+            while !done.load(Ordering::Relaxed) {
+                thread::park_timeout(RSS_SAMPLE_INTERVAL);
+                peak_rss = peak_rss.max(rss());
+            }
+            peak_rss
+        });
+
+        let (result, duration) = time_operation(operation);
+        done.store(true, Ordering::Relaxed);
+        sampler.thread().unpark();
+        (result, duration, sampler.join().unwrap())
+    });
+
+    let end_times = cpu_times();
+    let elapsed = |time: fn(&(f64, f64)) -> f64| {
+        Some(time(end_times.as_ref()?) - time(start_times.as_ref()?))
+    };
+    let profile = Profile {
+        duration,
+        peak_rss,
+        user_time: elapsed(|times| times.0),
+        system_time: elapsed(|times| times.1),
+    };
+    (result, profile)
+}
+
+/// The resident set size of the process in bytes.
+fn rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// The user and system CPU time of the process in seconds.
+fn cpu_times() -> Option<(f64, f64)> {
+    // `/proc` reports times in clock ticks, which are 1/100 s on Linux.
+    const TICKS_PER_SECOND: f64 = 100.0;
+
+    // The fields after the command name, which may contain spaces, start with the state.
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let ticks = |index: usize| fields.get(index)?.parse::<f64>().ok();
+    Some((ticks(11)? / TICKS_PER_SECOND, ticks(12)? / TICKS_PER_SECOND))
+}
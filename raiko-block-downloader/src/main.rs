@@ -1,22 +1,34 @@
+mod selection;
+
 use alloy_primitives::Address;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
+use block_download::{is_transient, DownloadSummary, RetryPolicy};
+use block_manifest::{find_block, BlockFormat, Manifest, ManifestEntry};
 use clap::Parser;
+use futures::stream::{self, StreamExt};
 use raiko_core::interfaces::ProofRequest;
 use raiko_core::provider::rpc::RpcBlockDataProvider;
 use raiko_core::Raiko;
-use raiko_lib::consts::SupportedChainSpecs;
-use raiko_lib::input::BlobProofType;
+use raiko_lib::consts::{ChainSpec, SupportedChainSpecs};
+use raiko_lib::input::{BlobProofType, GuestInput};
 use raiko_lib::primitives::B256;
 use raiko_lib::proof_type::ProofType;
+use rpc_cassette::Cassette;
+use selection::{BlockFilter, BlockSelector, Selection};
 use serde::Serialize;
 use serde_json::ser::{Formatter, PrettyFormatter};
 use serde_json::Serializer;
 use std::io::Write;
 use std::{
     fs::{self, File},
+    num::NonZeroUsize,
     path::PathBuf,
+    time::Duration,
 };
 
+/// The downloader recorded in the manifest of the blocks it writes.
+const DOWNLOADER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
 #[derive(Parser)]
 #[command(about = "Download Taiko blocks and save them to disk")]
 struct Args {
@@ -26,9 +38,66 @@ struct Args {
     #[arg(required = true)]
     l1_network: String,
 
-    /// List of block numbers to download
-    #[arg(required = true)]
+    /// List of block numbers to download. Block 0 has no parent to generate its input from
+    #[arg(
+        required_unless_present_any = ["from", "latest"],
+        conflicts_with_all = ["from", "latest"],
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
     block_numbers: Vec<u64>,
+
+    /// First block of a range of blocks to download
+    #[arg(long, conflicts_with = "latest", value_parser = clap::value_parser!(u64).range(1..))]
+    from: Option<u64>,
+
+    /// Last block of the range, the latest block if not given
+    #[arg(long, requires = "from", value_parser = clap::value_parser!(u64).range(1..))]
+    to: Option<u64>,
+
+    /// Download the N latest blocks that pass the filters
+    #[arg(long, value_name = "N")]
+    latest: Option<u64>,
+
+    /// Number of blocks back from the latest block that --latest looks at before giving up
+    #[arg(
+        long,
+        requires = "latest",
+        default_value_t = 10_000,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_scan_depth: u64,
+
+    /// Only download blocks with at least this many transactions, including the anchor transaction
+    #[arg(long)]
+    min_transactions: Option<usize>,
+
+    /// Only download blocks that used at least this much gas
+    #[arg(long)]
+    min_gas_used: Option<u64>,
+
+    /// Number of blocks downloaded, or headers read to select them, at the same time
+    #[arg(long, default_value = "4")]
+    concurrency: NonZeroUsize,
+
+    /// Number of times a download that failed on a transient RPC error is retried
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled after every retry
+    #[arg(long, default_value_t = 1000)]
+    backoff_ms: u64,
+
+    /// Download blocks again even if they are already on disk
+    #[arg(long)]
+    force: bool,
+
+    /// Record every RPC request and response to a cassette file
+    #[arg(long, value_name = "CASSETTE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer RPC requests from a recorded cassette file instead of the RPCs
+    #[arg(long, value_name = "CASSETTE")]
+    replay: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -52,57 +121,171 @@ async fn main() -> anyhow::Result<()> {
 
     let taiko_network = args.taiko_network;
     let l1_network = args.l1_network;
-    let taiko_chain_spec = chain_specs
+    let mut taiko_chain_spec = chain_specs
         .get_chain_spec(&taiko_network)
         .with_context(|| format!("Could not find chain spec for Taiko network '{}'", taiko_network))?;
-    let l1_chain_spec = chain_specs
+    let mut l1_chain_spec = chain_specs
         .get_chain_spec(&l1_network)
         .with_context(|| format!("Could not find chain spec for L1 network '{}'", l1_network))?;
 
+    // The manifest records the RPC the blocks come from, even when they are replayed.
+    let rpc = taiko_chain_spec.rpc.clone();
+    let cassette = match (&args.record, &args.replay) {
+        (Some(path), _) => Some(Cassette::record(path)?),
+        (None, Some(path)) => Some(Cassette::replay(path)?),
+        (None, None) => None,
+    };
+    if let Some(cassette) = &cassette {
+        taiko_chain_spec.rpc = cassette.serve("taiko", &taiko_chain_spec.rpc).await?;
+        l1_chain_spec.rpc = cassette.serve("l1", &l1_chain_spec.rpc).await?;
+        if let Some(beacon_rpc) = &l1_chain_spec.beacon_rpc {
+            l1_chain_spec.beacon_rpc = Some(cassette.serve("beacon", beacon_rpc).await?);
+        }
+    }
+
     // Create blocks directory in eval if it doesn't exist
     let blocks_dir = PathBuf::from(format!("eval/blocks-taiko_{taiko_network}"));
     fs::create_dir_all(&blocks_dir)?;
+    let mut manifest = Manifest::load(&blocks_dir)?.unwrap_or_default();
 
-    for block_number in args.block_numbers {
-        let provider =
-            RpcBlockDataProvider::new(&taiko_chain_spec.rpc, block_number - 1)
-                .expect("Could not create RpcBlockDataProvider");
+    let selection = match (args.from, args.latest) {
+        (Some(from), _) => Selection::Range { from, to: args.to },
+        (None, Some(count)) => Selection::Latest {
+            count,
+            max_scan_depth: args.max_scan_depth,
+        },
+        (None, None) => Selection::Blocks(args.block_numbers),
+    };
+    let filter = BlockFilter {
+        min_transactions: args.min_transactions,
+        min_gas_used: args.min_gas_used,
+    };
+    let block_numbers = BlockSelector::new(&taiko_chain_spec.rpc, filter, args.concurrency)?
+        .select(selection)
+        .await?;
 
-        let proof_request = ProofRequest {
-            block_number,
-            l1_inclusion_block_number: 0,
-            network: taiko_network.clone(),
-            graffiti: B256::ZERO,
-            prover: Address::ZERO,
-            proof_type: ProofType::Sp1,
-            l1_network: l1_network.clone(),
-            blob_proof_type: BlobProofType::ProofOfEquivalence,
-            prover_args: Default::default(),
-            gpu_number: None,
-        };
-        tracing::info!("Downloading block {}", block_number);
-        let raiko = Raiko::new(l1_chain_spec.clone(), taiko_chain_spec.clone(), proof_request.clone());
-        let input = raiko
-            .generate_input(provider)
-            .await
-            .expect("input generation failed");
-        let input_json = {
-            let mut buf = Vec::with_capacity(65536);
-            let fmt = CuteFormatter::new();
-            let mut serializer = Serializer::with_formatter(&mut buf, fmt);
-            input.serialize(&mut serializer).expect("Failed to serialize GuestInput");
-            String::from_utf8(buf).expect("Non-UTF8 serialization of GuestInput")
+    let mut summary = DownloadSummary::default();
+    let pending: Vec<u64> = block_numbers
+        .into_iter()
+        .filter(|block_number| {
+            // Blocks converted to another format by `eval convert` are downloaded too.
+            let on_disk = find_block(&blocks_dir, &block_number.to_string()).is_some();
+            let downloaded = !args.force && on_disk;
+            if downloaded {
+                tracing::info!("Skipping block {}, already downloaded", block_number);
+                summary.skipped += 1;
+            }
+            !downloaded
+        })
+        .collect();
+
+    let retry_policy = RetryPolicy {
+        retries: args.retries,
+        backoff: Duration::from_millis(args.backoff_ms),
+    };
+    let (taiko_network, l1_network) = (&taiko_network, &l1_network);
+    let (taiko_chain_spec, l1_chain_spec) = (&taiko_chain_spec, &l1_chain_spec);
+    let mut downloads = stream::iter(pending)
+        .map(|block_number| async move {
+            let label = format!("Block {}", block_number);
+            let input = retry_policy
+                .run(&label, is_transient, || {
+                    generate_input(
+                        taiko_chain_spec,
+                        l1_chain_spec,
+                        taiko_network,
+                        l1_network,
+                        block_number,
+                    )
+                })
+                .await;
+            (block_number, input)
+        })
+        .buffer_unordered(args.concurrency.get());
+
+    while let Some((block_number, input)) = downloads.next().await {
+        let input = match input {
+            Ok(input) => input,
+            Err(err) => {
+                tracing::error!("Failed to download block {}: {:#}", block_number, err);
+                summary.failed.push((block_number, format!("{:#}", err)));
+                continue;
+            }
         };
 
-        let file_path = blocks_dir.join(format!("{}.json", block_number));
-        let mut file = File::create(file_path)?;
+        // Blocks are only written as JSON, which `eval convert` rewrites to bincode with the
+        // `GuestInput` of its own raiko revision.
+        let bytes = to_json(&input)?;
 
-        file.write_all(input_json.as_bytes())?;
+        let file_name = BlockFormat::Json.file_name(&block_number.to_string());
+        let mut file = File::create(blocks_dir.join(&file_name))?;
+
+        file.write_all(&bytes)?;
+
+        let entry = ManifestEntry::new(
+            block_number,
+            taiko_network,
+            Some(l1_network),
+            DOWNLOADER,
+            &rpc,
+            &bytes,
+        );
+        manifest.blocks.insert(file_name, entry);
+        // Saved after every block, so an interrupted download keeps the provenance of the blocks
+        // that were written.
+        manifest.save(&blocks_dir)?;
 
         tracing::info!("Successfully saved block {}", block_number);
+        summary.downloaded += 1;
+    }
+
+    if let Some(cassette) = &cassette {
+        cassette.save()?;
     }
+    Ok(summary.finish()?)
+}
+
+/// Generates the `GuestInput` of a block from the Taiko and L1 RPCs.
+async fn generate_input(
+    taiko_chain_spec: &ChainSpec,
+    l1_chain_spec: &ChainSpec,
+    taiko_network: &str,
+    l1_network: &str,
+    block_number: u64,
+) -> anyhow::Result<GuestInput> {
+    let provider = RpcBlockDataProvider::new(&taiko_chain_spec.rpc, block_number - 1)
+        .map_err(|err| anyhow!("Could not create RpcBlockDataProvider: {}", err))?;
+
+    let proof_request = ProofRequest {
+        block_number,
+        l1_inclusion_block_number: 0,
+        network: taiko_network.to_string(),
+        graffiti: B256::ZERO,
+        prover: Address::ZERO,
+        proof_type: ProofType::Sp1,
+        l1_network: l1_network.to_string(),
+        blob_proof_type: BlobProofType::ProofOfEquivalence,
+        prover_args: Default::default(),
+        gpu_number: None,
+    };
+    tracing::info!("Downloading block {}", block_number);
+    let raiko = Raiko::new(
+        l1_chain_spec.clone(),
+        taiko_chain_spec.clone(),
+        proof_request,
+    );
+    raiko
+        .generate_input(provider)
+        .await
+        .map_err(|err| anyhow!("input generation failed: {}", err))
+}
 
-    Ok(())
+fn to_json(input: &GuestInput) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(65536);
+    let fmt = CuteFormatter::new();
+    let mut serializer = Serializer::with_formatter(&mut buf, fmt);
+    input.serialize(&mut serializer)?;
+    Ok(buf)
 }
 
 /// Like [PrettyFormatter], but places array element on the same line.
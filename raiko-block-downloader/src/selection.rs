@@ -0,0 +1,162 @@
+use std::num::NonZeroUsize;
+
+use alloy_provider::{Provider, ReqwestProvider};
+use anyhow::Context;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use url::Url;
+
+/// The filters a block must pass to be downloaded.
+pub struct BlockFilter {
+    /// The minimum number of transactions, including the anchor transaction of Taiko blocks.
+    pub min_transactions: Option<usize>,
+    /// The minimum gas used.
+    pub min_gas_used: Option<u64>,
+}
+
+impl BlockFilter {
+    fn is_empty(&self) -> bool {
+        self.min_transactions.is_none() && self.min_gas_used.is_none()
+    }
+}
+
+/// How the blocks to download are chosen.
+pub enum Selection {
+    /// An explicit list of blocks.
+    Blocks(Vec<u64>),
+    /// An inclusive range of blocks, up to the latest block if it has no end.
+    Range { from: u64, to: Option<u64> },
+    /// The given number of latest blocks that pass the filters, looking at most `max_scan_depth`
+    /// blocks back from the latest block.
+    Latest { count: u64, max_scan_depth: u64 },
+}
+
+/// Reads the headers of the Taiko chain to turn a [Selection] into the block numbers to download.
+pub struct BlockSelector {
+    provider: ReqwestProvider,
+    filter: BlockFilter,
+    /// The number of headers read at the same time.
+    concurrency: usize,
+}
+
+impl BlockSelector {
+    pub fn new(rpc: &str, filter: BlockFilter, concurrency: NonZeroUsize) -> anyhow::Result<Self> {
+        let rpc_url = Url::parse(rpc).with_context(|| format!("Invalid RPC URL '{}'", rpc))?;
+        Ok(BlockSelector {
+            provider: ReqwestProvider::new_http(rpc_url),
+            filter,
+            concurrency: concurrency.get(),
+        })
+    }
+
+    pub async fn select(&self, selection: Selection) -> anyhow::Result<Vec<u64>> {
+        let block_numbers = match selection {
+            Selection::Blocks(block_numbers) => self.filter(block_numbers).await?,
+            Selection::Range { from, to } => {
+                let to = match to {
+                    Some(to) => to,
+                    None => self.provider.get_block_number().await?,
+                };
+                anyhow::ensure!(from <= to, "Empty block range {}..={}", from, to);
+                self.filter(from..=to).await?
+            }
+            Selection::Latest {
+                count,
+                max_scan_depth,
+            } => self.latest(count, max_scan_depth).await?,
+        };
+
+        tracing::info!("Selected {} blocks", block_numbers.len());
+        Ok(block_numbers)
+    }
+
+    /// Keeps the blocks that pass the filters, in the order they are given. Up to `concurrency`
+    /// headers are read at the same time.
+    async fn filter(
+        &self,
+        block_numbers: impl IntoIterator<Item = u64>,
+    ) -> anyhow::Result<Vec<u64>> {
+        let mut checked: Vec<(usize, u64, bool)> =
+            stream::iter(block_numbers.into_iter().enumerate())
+                .map(|(index, block_number)| async move {
+                    let passes = self.passes(block_number).await?;
+                    anyhow::Ok((index, block_number, passes))
+                })
+                .buffer_unordered(self.concurrency)
+                .try_collect()
+                .await?;
+        checked.sort_unstable_by_key(|(index, _, _)| *index);
+
+        Ok(checked
+            .into_iter()
+            .filter(|(_, _, passes)| *passes)
+            .map(|(_, block_number, _)| block_number)
+            .collect())
+    }
+
+    /// Walks back from the latest block, `concurrency` blocks at a time, until `count` blocks pass
+    /// the filters. Block 0 is never selected, as its parent is needed to generate the input.
+    ///
+    /// Fails if fewer than `count` blocks pass the filters among the `max_scan_depth` latest blocks,
+    /// so filters that few blocks pass do not read the headers of the whole chain.
+    async fn latest(&self, count: u64, max_scan_depth: u64) -> anyhow::Result<Vec<u64>> {
+        let latest = self.provider.get_block_number().await?;
+        let lowest = latest
+            .saturating_sub(max_scan_depth.saturating_sub(1))
+            .max(1);
+        let mut newest = latest;
+        let mut selected = Vec::new();
+        while (selected.len() as u64) < count && newest >= lowest {
+            let oldest = newest
+                .saturating_sub(self.concurrency as u64 - 1)
+                .max(lowest);
+            let remaining = count as usize - selected.len();
+            let passed = self.filter((oldest..=newest).rev()).await?;
+            selected.extend(passed.into_iter().take(remaining));
+            newest = oldest - 1;
+        }
+        if (selected.len() as u64) < count {
+            anyhow::ensure!(
+                lowest == 1,
+                "Only {} of {} blocks pass the filters in the {} latest blocks, raise \
+                 --max-scan-depth to look further back",
+                selected.len(),
+                count,
+                max_scan_depth
+            );
+            tracing::warn!("Only {} blocks pass the filters", selected.len());
+        }
+
+        selected.reverse();
+        Ok(selected)
+    }
+
+    async fn passes(&self, block_number: u64) -> anyhow::Result<bool> {
+        if self.filter.is_empty() {
+            return Ok(true);
+        }
+
+        let block = self
+            .provider
+            .get_block_by_number(block_number.into(), false)
+            .await?
+            .with_context(|| format!("Block {} not found", block_number))?;
+        let transactions = block.transactions.len();
+        let gas_used = block.header.gas_used as u64;
+
+        let BlockFilter {
+            min_transactions,
+            min_gas_used,
+        } = self.filter;
+        let passes = min_transactions.is_none_or(|min| transactions >= min)
+            && min_gas_used.is_none_or(|min| gas_used >= min);
+        if !passes {
+            tracing::info!(
+                "Skipping block {} ({} transactions, {} gas used)",
+                block_number,
+                transactions,
+                gas_used
+            );
+        }
+        Ok(passes)
+    }
+}
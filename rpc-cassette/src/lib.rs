@@ -0,0 +1,300 @@
+//! Records the requests the block downloaders make to their RPCs into a cassette file and replays
+//! them later, so the input of a block can be generated again without a live RPC.
+//!
+//! Each RPC is put behind a local stand-in server and the downloader is pointed at the server
+//! instead of the RPC. When recording, the server forwards every request to the RPC and keeps the
+//! response, appending each new interaction to the cassette file before answering the downloader,
+//! so a download that fails or is interrupted keeps what it recorded. When replaying, it answers
+//! from the cassette alone.
+//!
+//! A cassette file holds one interaction per line, as JSON.
+
+mod server;
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    iter,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use server::Endpoint;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CassetteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("request to the RPC failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("malformed HTTP request: {0}")]
+    MalformedRequest(String),
+    #[error("the cassette writer stopped")]
+    WriterStopped,
+}
+
+/// Whether the stand-in servers forward requests to the RPCs or answer from the cassette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+/// A request made to an RPC and its response. Requests are told apart by their content, so the
+/// ids of JSON-RPC requests are not recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    /// A JSON-RPC call and its response without the id.
+    JsonRpc {
+        endpoint: String,
+        method: String,
+        params: Value,
+        response: Value,
+    },
+    /// A plain HTTP GET, such as a request to the beacon API.
+    Get {
+        endpoint: String,
+        path: String,
+        status: u16,
+        body: String,
+    },
+}
+
+/// The interactions of a cassette, indexed by what requests are matched on.
+#[derive(Default)]
+pub(crate) struct Interactions {
+    /// The responses of JSON-RPC calls, by endpoint, method and params. The params are keyed by
+    /// their JSON, which is canonical as objects keep their keys sorted.
+    calls: HashMap<(String, String, String), Value>,
+    /// The status and body of GETs, by endpoint and path.
+    gets: HashMap<(String, String), (u16, String)>,
+}
+
+impl Interactions {
+    /// Adds an interaction, unless one matching the same requests is there already. Returns
+    /// whether it was added.
+    pub fn insert(&mut self, interaction: &Interaction) -> bool {
+        match interaction {
+            Interaction::JsonRpc {
+                endpoint,
+                method,
+                params,
+                response,
+            } => match self
+                .calls
+                .entry((endpoint.clone(), method.clone(), params.to_string()))
+            {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(entry) => {
+                    entry.insert(response.clone());
+                    true
+                }
+            },
+            Interaction::Get {
+                endpoint,
+                path,
+                status,
+                body,
+            } => match self.gets.entry((endpoint.clone(), path.clone())) {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(entry) => {
+                    entry.insert((*status, body.clone()));
+                    true
+                }
+            },
+        }
+    }
+
+    /// The recorded response of a JSON-RPC call, without its id.
+    pub fn call(&self, endpoint: &str, method: &str, params: &Value) -> Option<&Value> {
+        let key = (endpoint.to_string(), method.to_string(), params.to_string());
+        self.calls.get(&key)
+    }
+
+    /// The recorded status and body of a GET.
+    pub fn get(&self, endpoint: &str, path: &str) -> Option<&(u16, String)> {
+        self.gets.get(&(endpoint.to_string(), path.to_string()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len() + self.gets.len()
+    }
+}
+
+/// Reads the interactions of a cassette file. A download interrupted while writing may leave the
+/// last line truncated, so it is skipped if it does not parse.
+fn read_cassette(path: &Path) -> Result<Interactions, CassetteError> {
+    let contents = fs::read_to_string(path)?;
+    let lines: Vec<_> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    let mut interactions = Interactions::default();
+    for (index, line) in lines.iter().enumerate() {
+        let interaction = match serde_json::from_str(line) {
+            Ok(interaction) => interaction,
+            Err(err) if index + 1 == lines.len() => {
+                tracing::warn!(
+                    "Skipping the truncated last line of {}: {}",
+                    path.display(),
+                    err
+                );
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        interactions.insert(&interaction);
+    }
+    Ok(interactions)
+}
+
+/// An interaction to append to the cassette file and the sender to acknowledge it with.
+type Pending = (Interaction, oneshot::Sender<io::Result<()>>);
+
+/// Appends recorded interactions to the cassette file on a thread of its own, so the stand-in
+/// servers never block on the file. The interactions sent while a batch is written are written
+/// together in the next one.
+pub(crate) struct CassetteWriter {
+    sender: mpsc::Sender<Pending>,
+}
+
+impl CassetteWriter {
+    /// Creates the cassette file, replacing any file at `path`, and starts the writer thread.
+    fn create(path: &Path) -> Result<Self, CassetteError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let (sender, received) = mpsc::channel::<Pending>();
+        thread::Builder::new()
+            .name("cassette-writer".to_string())
+            .spawn(move || {
+                while let Ok(first) = received.recv() {
+                    let batch: Vec<_> = iter::once(first).chain(received.try_iter()).collect();
+                    let written =
+                        write_lines(&mut file, batch.iter().map(|(interaction, _)| interaction));
+                    for (_, ack) in batch {
+                        let result = match &written {
+                            Ok(()) => Ok(()),
+                            Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+                        };
+                        let _ = ack.send(result);
+                    }
+                }
+            })?;
+        Ok(CassetteWriter { sender })
+    }
+
+    /// Appends an interaction to the cassette file, returning once it is written.
+    pub async fn write(&self, interaction: Interaction) -> Result<(), CassetteError> {
+        let (ack, written) = oneshot::channel();
+        self.sender
+            .send((interaction, ack))
+            .map_err(|_| CassetteError::WriterStopped)?;
+        written.await.map_err(|_| CassetteError::WriterStopped)??;
+        Ok(())
+    }
+}
+
+/// Writes interactions as lines of JSON and flushes them.
+fn write_lines<'a>(
+    file: &mut BufWriter<File>,
+    interactions: impl Iterator<Item = &'a Interaction>,
+) -> io::Result<()> {
+    for interaction in interactions {
+        serde_json::to_writer(&mut *file, interaction)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()
+}
+
+/// The interactions with the RPCs of one download, shared by the stand-in servers.
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Arc<Mutex<Interactions>>,
+    /// The writer of the cassette file, when recording.
+    writer: Option<Arc<CassetteWriter>>,
+}
+
+impl Cassette {
+    /// Starts an empty cassette at `path`, which each new interaction is appended to as it is
+    /// recorded.
+    pub fn record(path: impl Into<PathBuf>) -> Result<Self, CassetteError> {
+        let path = path.into();
+        let writer = CassetteWriter::create(&path)?;
+        Ok(Cassette {
+            path,
+            mode: Mode::Record,
+            interactions: Default::default(),
+            writer: Some(Arc::new(writer)),
+        })
+    }
+
+    /// Reads a recorded cassette to answer requests from.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, CassetteError> {
+        let path = path.into();
+        let interactions = read_cassette(&path)?;
+        tracing::info!(
+            "Replaying {} interactions from {}",
+            interactions.len(),
+            path.display()
+        );
+        Ok(Cassette {
+            path,
+            mode: Mode::Replay,
+            interactions: Arc::new(Mutex::new(interactions)),
+            writer: None,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts a local server standing in for the RPC at `upstream` and returns its URL. The
+    /// `endpoint` names the RPC in the cassette, so one cassette can hold several RPCs.
+    pub async fn serve(&self, endpoint: &str, upstream: &str) -> Result<String, CassetteError> {
+        let endpoint = Endpoint {
+            name: endpoint.to_string(),
+            upstream: upstream.to_string(),
+            mode: self.mode,
+            interactions: self.interactions.clone(),
+            writer: self.writer.clone(),
+            client: reqwest::Client::new(),
+        };
+        let url = endpoint.bind().await?;
+        tracing::info!("Standing in for {} at {}", upstream, url);
+        Ok(url)
+    }
+
+    /// Logs the number of recorded interactions. The stand-in servers write every new interaction
+    /// before answering it, so the cassette file is complete already. Does nothing when replaying.
+    pub fn save(&self) -> Result<(), CassetteError> {
+        if self.mode == Mode::Replay {
+            return Ok(());
+        }
+
+        tracing::info!(
+            "Recorded {} interactions to {}",
+            self.interactions.lock().unwrap().len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,521 @@
+use std::sync::{Arc, Mutex};
+
+use reqwest::{header::CONTENT_TYPE, Method, StatusCode};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{CassetteError, CassetteWriter, Interaction, Interactions, Mode};
+
+/// A local server standing in for one RPC.
+pub(crate) struct Endpoint {
+    pub name: String,
+    pub upstream: String,
+    pub mode: Mode,
+    pub interactions: Arc<Mutex<Interactions>>,
+    /// The writer of the cassette file, when recording.
+    pub writer: Option<Arc<CassetteWriter>>,
+    pub client: reqwest::Client,
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+struct HttpResponse {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Endpoint {
+    /// Listens on a free local port and returns the URL of the server.
+    pub async fn bind(self) -> Result<String, CassetteError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let endpoint = Arc::new(self);
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let endpoint = endpoint.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = endpoint.handle(stream).await {
+                        tracing::warn!("Stand-in for {} failed: {}", endpoint.name, err);
+                    }
+                });
+            }
+        });
+        Ok(url)
+    }
+
+    /// Answers the request of a connection and closes it.
+    async fn handle(&self, stream: TcpStream) -> Result<(), CassetteError> {
+        let mut stream = BufReader::new(stream);
+        let Some(request) = read_request(&mut stream).await? else {
+            return Ok(());
+        };
+
+        let response = match self.respond(&request).await {
+            Ok(response) => response,
+            Err(err) => {
+                // A failed request is reported to the downloader, which can retry it.
+                tracing::warn!("{} {} failed: {}", request.method, request.path, err);
+                HttpResponse {
+                    status: StatusCode::BAD_GATEWAY.as_u16(),
+                    body: err.to_string().into_bytes(),
+                }
+            }
+        };
+
+        let reason = StatusCode::from_u16(response.status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or_default();
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            response.status,
+            reason,
+            response.body.len()
+        );
+        let stream = stream.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&response.body).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
+    async fn respond(&self, request: &HttpRequest) -> Result<HttpResponse, CassetteError> {
+        match request.method.as_str() {
+            "POST" => self.json_rpc(request).await,
+            "GET" => self.get(request).await,
+            method => Err(CassetteError::MalformedRequest(format!(
+                "unsupported method {method}"
+            ))),
+        }
+    }
+
+    /// Answers a JSON-RPC call or batch of calls.
+    async fn json_rpc(&self, request: &HttpRequest) -> Result<HttpResponse, CassetteError> {
+        let body: Value = serde_json::from_slice(&request.body)?;
+        let calls = match &body {
+            Value::Array(calls) => calls.iter().collect(),
+            call => vec![call],
+        };
+
+        if self.mode == Mode::Record {
+            let response = self.forward(Method::POST, request).await?;
+            if response.status == StatusCode::OK.as_u16() {
+                let responses = match serde_json::from_slice(&response.body)? {
+                    Value::Array(responses) => responses,
+                    response => vec![response],
+                };
+                for call in calls {
+                    let response = responses
+                        .iter()
+                        .find(|response| response["id"] == call["id"]);
+                    // Errors are left out, as they are often transient.
+                    if let Some(response) = response.filter(|response| response["error"].is_null())
+                    {
+                        self.record_call(call, response).await?;
+                    }
+                }
+            }
+            return Ok(response);
+        }
+
+        let mut responses: Vec<_> = calls
+            .into_iter()
+            .map(|call| self.replay_call(call))
+            .collect();
+        let body = match body {
+            Value::Array(_) => Value::Array(responses),
+            _ => responses.remove(0),
+        };
+        Ok(HttpResponse {
+            status: StatusCode::OK.as_u16(),
+            body: serde_json::to_vec(&body)?,
+        })
+    }
+
+    async fn record_call(&self, call: &Value, response: &Value) -> Result<(), CassetteError> {
+        let mut response = response.clone();
+        if let Some(response) = response.as_object_mut() {
+            response.remove("id");
+        }
+        let interaction = Interaction::JsonRpc {
+            endpoint: self.name.clone(),
+            method: call["method"].as_str().unwrap_or_default().to_string(),
+            params: call["params"].clone(),
+            response,
+        };
+        self.record(interaction).await
+    }
+
+    /// Adds a new interaction to the cassette and appends it to the cassette file, so it is kept
+    /// even if the download fails later. Interactions that are in the cassette already are not
+    /// recorded again.
+    async fn record(&self, interaction: Interaction) -> Result<(), CassetteError> {
+        if !self.interactions.lock().unwrap().insert(&interaction) {
+            return Ok(());
+        }
+        match &self.writer {
+            Some(writer) => writer.write(interaction).await,
+            None => Ok(()),
+        }
+    }
+
+    fn replay_call(&self, call: &Value) -> Value {
+        let method = call["method"].as_str().unwrap_or_default();
+        let interactions = self.interactions.lock().unwrap();
+        match interactions.call(&self.name, method, &call["params"]) {
+            Some(response) => {
+                let mut response = response.clone();
+                response["id"] = call["id"].clone();
+                response
+            }
+            None => {
+                tracing::warn!("{} call {} is not in the cassette", self.name, method);
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": call["id"],
+                    "error": {
+                        "code": -32000,
+                        "message": format!("{method} is not in the cassette"),
+                    },
+                })
+            }
+        }
+    }
+
+    /// Answers a plain HTTP GET, such as a request to the beacon API.
+    async fn get(&self, request: &HttpRequest) -> Result<HttpResponse, CassetteError> {
+        if self.mode == Mode::Record {
+            let response = self.forward(Method::GET, request).await?;
+            if StatusCode::from_u16(response.status).is_ok_and(|status| status.is_success()) {
+                let interaction = Interaction::Get {
+                    endpoint: self.name.clone(),
+                    path: request.path.clone(),
+                    status: response.status,
+                    body: String::from_utf8_lossy(&response.body).into_owned(),
+                };
+                self.record(interaction).await?;
+            }
+            return Ok(response);
+        }
+
+        let interactions = self.interactions.lock().unwrap();
+        match interactions.get(&self.name, &request.path) {
+            Some((status, body)) => Ok(HttpResponse {
+                status: *status,
+                body: body.clone().into_bytes(),
+            }),
+            None => {
+                tracing::warn!("{} GET {} is not in the cassette", self.name, request.path);
+                Ok(HttpResponse {
+                    status: StatusCode::NOT_FOUND.as_u16(),
+                    body: format!("{} is not in the cassette", request.path).into_bytes(),
+                })
+            }
+        }
+    }
+
+    /// Sends a request to the RPC, at the same path relative to the URL of the RPC.
+    async fn forward(
+        &self,
+        method: Method,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, CassetteError> {
+        let url = match request.path.as_str() {
+            "/" => self.upstream.clone(),
+            path => format!("{}{}", self.upstream.trim_end_matches('/'), path),
+        };
+        let response = self
+            .client
+            .request(method, url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request.body.clone())
+            .send()
+            .await?;
+        Ok(HttpResponse {
+            status: response.status().as_u16(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+}
+
+/// Reads an HTTP/1.1 request with a `Content-Length` body, `None` if the connection was closed
+/// without one.
+async fn read_request(
+    stream: &mut BufReader<TcpStream>,
+) -> Result<Option<HttpRequest>, CassetteError> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut request_line = line.split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(CassetteError::MalformedRequest(line.trim_end().to_string()));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| {
+                    CassetteError::MalformedRequest(format!("invalid header {header}"))
+                })?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+    Ok(Some(HttpRequest { method, path, body }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::Path,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::Cassette;
+
+    /// An address nothing listens on, for replays that must not reach the RPC.
+    const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+    /// Starts a fake RPC that answers every JSON-RPC call with its method and params and every GET
+    /// with its path. Returns its URL and the number of requests it answered.
+    async fn upstream() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let Some(request) = read_request(&mut stream).await.unwrap() else {
+                    continue;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let answer = |call: &Value| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "result": { "method": call["method"], "params": call["params"] },
+                    })
+                };
+                let body = match request.method.as_str() {
+                    "POST" => match serde_json::from_slice(&request.body).unwrap() {
+                        Value::Array(calls) => Value::Array(calls.iter().map(answer).collect()),
+                        call => answer(&call),
+                    },
+                    _ => json!({ "path": request.path }),
+                };
+                let body = serde_json::to_vec(&body).unwrap();
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let stream = stream.get_mut();
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(&body).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    async fn post(url: &str, body: Value) -> Value {
+        let response = reqwest::Client::new()
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body).unwrap())
+            .send()
+            .await
+            .unwrap();
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap()
+    }
+
+    async fn get(url: &str) -> (StatusCode, String) {
+        let response = reqwest::get(url).await.unwrap();
+        (response.status(), response.text().await.unwrap())
+    }
+
+    fn call(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn recorded(path: &Path) -> Vec<Interaction> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.ndjson");
+        let (upstream, requests) = upstream().await;
+
+        let cassette = Cassette::record(&path).unwrap();
+        let url = cassette.serve("l1", &upstream).await.unwrap();
+        let block = post(&url, call(1, "eth_getBlockByNumber", json!(["0x1", false]))).await;
+        let batch = json!([
+            call(2, "eth_chainId", json!([])),
+            call(3, "eth_blockNumber", json!([]))
+        ]);
+        let batch_responses = post(&url, batch).await;
+        cassette.save().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let url = cassette.serve("l1", UNREACHABLE).await.unwrap();
+        let replayed = post(&url, call(1, "eth_getBlockByNumber", json!(["0x1", false]))).await;
+        assert_eq!(replayed, block);
+        // Calls recorded in a batch are replayed one at a time, with the ids of the new calls.
+        let replayed = post(&url, call(9, "eth_blockNumber", json!([]))).await;
+        assert_eq!(replayed["result"], batch_responses[1]["result"]);
+        assert_eq!(replayed["id"], 9);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn matches_calls_by_endpoint_method_and_params() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.ndjson");
+        let (upstream, _) = upstream().await;
+
+        let cassette = Cassette::record(&path).unwrap();
+        let url = cassette.serve("l1", &upstream).await.unwrap();
+        post(&url, call(1, "eth_getBalance", json!(["0xab", "latest"]))).await;
+        cassette.save().unwrap();
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let l1 = cassette.serve("l1", UNREACHABLE).await.unwrap();
+        let taiko = cassette.serve("taiko", UNREACHABLE).await.unwrap();
+        let replayed = post(&l1, call(1, "eth_getBalance", json!(["0xab", "latest"]))).await;
+        assert_eq!(replayed["result"]["params"], json!(["0xab", "latest"]));
+
+        for (url, method, params) in [
+            (&taiko, "eth_getBalance", json!(["0xab", "latest"])),
+            (&l1, "eth_getCode", json!(["0xab", "latest"])),
+            (&l1, "eth_getBalance", json!(["0xab", "0x1"])),
+        ] {
+            let replayed = post(url, call(1, method, params)).await;
+            assert!(
+                replayed["result"].is_null(),
+                "{method} on {url} was replayed"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_missing_calls_with_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.ndjson");
+        fs::write(&path, "").unwrap();
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let url = cassette.serve("l1", UNREACHABLE).await.unwrap();
+        let replayed = post(&url, call(4, "eth_chainId", json!([]))).await;
+        assert_eq!(replayed["id"], 4);
+        assert_eq!(replayed["error"]["code"], -32000);
+        assert_eq!(
+            replayed["error"]["message"],
+            "eth_chainId is not in the cassette"
+        );
+    }
+
+    #[tokio::test]
+    async fn matches_gets_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.ndjson");
+        let (upstream, _) = upstream().await;
+
+        let cassette = Cassette::record(&path).unwrap();
+        let url = cassette.serve("beacon", &upstream).await.unwrap();
+        let (status, genesis) = get(&format!("{url}/eth/v1/beacon/genesis")).await;
+        assert_eq!(status, StatusCode::OK);
+        cassette.save().unwrap();
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let url = cassette.serve("beacon", UNREACHABLE).await.unwrap();
+        assert_eq!(
+            get(&format!("{url}/eth/v1/beacon/genesis")).await,
+            (StatusCode::OK, genesis)
+        );
+        let (status, _) = get(&format!("{url}/eth/v1/beacon/blob_sidecars/1")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn writes_every_new_interaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassettes").join("cassette.ndjson");
+        let (upstream, _) = upstream().await;
+
+        let cassette = Cassette::record(&path).unwrap();
+        let url = cassette.serve("l1", &upstream).await.unwrap();
+        post(&url, call(1, "eth_chainId", json!([]))).await;
+        assert_eq!(recorded(&path).len(), 1);
+
+        // A repeated call is not recorded again.
+        post(&url, call(2, "eth_chainId", json!([]))).await;
+        get(&format!("{url}/eth/v1/beacon/genesis")).await;
+        let interactions = recorded(&path);
+        assert_eq!(interactions.len(), 2);
+        let Interaction::Get { path, .. } = &interactions[1] else {
+            panic!("the GET was not recorded");
+        };
+        assert_eq!(path, "/eth/v1/beacon/genesis");
+    }
+
+    #[tokio::test]
+    async fn skips_a_truncated_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.ndjson");
+        let (upstream, _) = upstream().await;
+
+        let cassette = Cassette::record(&path).unwrap();
+        let url = cassette.serve("l1", &upstream).await.unwrap();
+        let recorded = post(&url, call(1, "eth_chainId", json!([]))).await;
+        let mut contents = fs::read_to_string(&path).unwrap();
+        contents.push_str(r#"{"kind":"json_rpc","endpoint":"l1","met"#);
+        fs::write(&path, &contents).unwrap();
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let url = cassette.serve("l1", UNREACHABLE).await.unwrap();
+        assert_eq!(
+            post(&url, call(1, "eth_chainId", json!([]))).await,
+            recorded
+        );
+
+        // Only the last line may be truncated.
+        contents.push_str("\n{}\n");
+        fs::write(&path, &contents).unwrap();
+        assert!(Cassette::replay(&path).is_err());
+    }
+}